@@ -0,0 +1,323 @@
+use super::{Decoder, EncodeBuf, Encoder};
+use aead::{AeadCore, AeadInPlace, Nonce, consts::U12};
+use bytes::{Bytes, BytesMut};
+
+const COUNTER_LEN: usize = 8;
+
+// Each side seals with its own nonce prefix so that both directions can share a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Server,
+}
+
+impl Side {
+    const fn prefix(self) -> [u8; 4] {
+        match self {
+            Side::Client => *b"clnt",
+            Side::Server => *b"srvr",
+        }
+    }
+
+    const fn peer(self) -> Side {
+        match self {
+            Side::Client => Side::Server,
+            Side::Server => Side::Client,
+        }
+    }
+}
+
+pub struct AeadCodec<C, A> {
+    inner: C,
+    cipher: A,
+    side: Side,
+    send_counter: u64,
+    recv_counter: u64,
+}
+
+#[cfg(feature = "chacha20")]
+pub type ChaCha20Poly1305Codec<C> = AeadCodec<C, chacha20poly1305::ChaCha20Poly1305>;
+
+#[cfg(feature = "aesgcm")]
+pub type Aes256GcmCodec<C> = AeadCodec<C, aes_gcm::Aes256Gcm>;
+
+impl<C, A> AeadCodec<C, A> {
+    #[allow(missing_docs)]
+    pub const fn new(inner: C, cipher: A, side: Side) -> Self {
+        Self {
+            inner,
+            cipher,
+            side,
+            send_counter: 0,
+            recv_counter: 0,
+        }
+    }
+}
+
+impl<C: std::fmt::Debug, A> std::fmt::Debug for AeadCodec<C, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AeadCodec")
+            .field("inner", &self.inner)
+            .field("cipher", &std::any::type_name::<A>())
+            .field("side", &self.side)
+            .field("send_counter", &self.send_counter)
+            .field("recv_counter", &self.recv_counter)
+            .finish()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AeadError<E: std::error::Error + 'static> {
+    #[error("frame failed authentication")]
+    Authentication,
+    #[error("failed to seal frame")]
+    Seal,
+    #[error("replayed frame (counter {0})")]
+    Replayed(u64),
+    #[error("frame out of order (expected counter {expected}, got {actual})")]
+    OutOfOrder { expected: u64, actual: u64 },
+    #[error("frame too short to carry a nonce counter ({0} bytes)")]
+    Truncated(usize),
+    #[error("nonce counter exhausted")]
+    CounterExhausted,
+    #[error(transparent)]
+    Inner(#[from] E),
+}
+
+fn nonce<A: AeadCore<NonceSize = U12>>(side: Side, counter: u64) -> Nonce<A> {
+    let mut nonce = Nonce::<A>::default();
+    nonce[.. 4].copy_from_slice(&side.prefix());
+    nonce[4 ..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+impl<C, A> Encoder for AeadCodec<C, A>
+where
+    C: Encoder<Item = Bytes>,
+    A: AeadInPlace + AeadCore<NonceSize = U12>,
+{
+    type Error = AeadError<C::Error>;
+    type Item = Bytes;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let frame = self.seal(&src)?;
+        self.inner.encode(frame, dst)?;
+        Ok(())
+    }
+
+    fn encode_vectored(&mut self, src: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        let frame = self.seal(&src)?;
+        self.inner.encode_vectored(frame, dst)?;
+        Ok(())
+    }
+}
+
+impl<C, A> AeadCodec<C, A>
+where
+    C: Encoder,
+    A: AeadInPlace + AeadCore<NonceSize = U12>,
+{
+    fn seal(&mut self, src: &[u8]) -> Result<Bytes, AeadError<C::Error>> {
+        let counter = self.send_counter;
+        self.send_counter = counter.checked_add(1).ok_or(AeadError::CounterExhausted)?;
+
+        let mut frame = BytesMut::with_capacity(COUNTER_LEN + src.len() + 16);
+        frame.extend_from_slice(&counter.to_be_bytes());
+        frame.extend_from_slice(src);
+        let tag = self
+            .cipher
+            .encrypt_in_place_detached(&nonce::<A>(self.side, counter), &[], &mut frame[COUNTER_LEN ..])
+            .map_err(|_| AeadError::Seal)?;
+        frame.extend_from_slice(&tag);
+        Ok(frame.freeze())
+    }
+}
+
+impl<C, A> Decoder for AeadCodec<C, A>
+where
+    C: Decoder<Item = Bytes>,
+    A: AeadInPlace + AeadCore<NonceSize = U12>,
+{
+    type Error = AeadError<C::Error>;
+    type Item = Bytes;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode(src)? {
+            Some(frame) => self.open(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode_eof(src)? {
+            Some(frame) => self.open(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // A frame that fails to open leaves the receive counter alone, so the frames after it still
+    // open. Once a frame goes missing, though, no later counter will ever match again.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        match err {
+            AeadError::Authentication | AeadError::Replayed(_) | AeadError::Truncated(_) => true,
+            AeadError::Inner(err) => self.inner.is_recoverable(err),
+            _ => false,
+        }
+    }
+}
+
+impl<C, A> AeadCodec<C, A>
+where
+    C: Decoder,
+    A: AeadInPlace + AeadCore<NonceSize = U12>,
+{
+    fn open(&mut self, frame: Bytes) -> Result<Bytes, AeadError<C::Error>> {
+        if frame.len() < COUNTER_LEN {
+            return Err(AeadError::Truncated(frame.len()));
+        }
+        let mut counter = [0u8; COUNTER_LEN];
+        counter.copy_from_slice(&frame[.. COUNTER_LEN]);
+        let counter = u64::from_be_bytes(counter);
+
+        // The counter is bound to the ciphertext through the nonce, so it is only trusted once
+        // the frame has been authenticated.
+        let mut opened = frame[COUNTER_LEN ..].to_vec();
+        self.cipher
+            .decrypt_in_place(&nonce::<A>(self.side.peer(), counter), &[], &mut opened)
+            .map_err(|_| AeadError::Authentication)?;
+
+        if counter < self.recv_counter {
+            return Err(AeadError::Replayed(counter));
+        }
+        if counter > self.recv_counter {
+            return Err(AeadError::OutOfOrder {
+                expected: self.recv_counter,
+                actual: counter,
+            });
+        }
+        self.recv_counter += 1;
+
+        Ok(Bytes::from(opened))
+    }
+}
+
+#[cfg(all(test, feature = "chacha20"))]
+mod tests {
+    use super::*;
+    use crate::codec::LengthCodec;
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+
+    fn pair() -> (
+        ChaCha20Poly1305Codec<LengthCodec<u32>>,
+        ChaCha20Poly1305Codec<LengthCodec<u32>>,
+    ) {
+        let key = [7u8; 32];
+        let client = AeadCodec::new(
+            LengthCodec::new(),
+            ChaCha20Poly1305::new_from_slice(&key).unwrap(),
+            Side::Client,
+        );
+        let server = AeadCodec::new(
+            LengthCodec::new(),
+            ChaCha20Poly1305::new_from_slice(&key).unwrap(),
+            Side::Server,
+        );
+        (client, server)
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let (mut client, mut server) = pair();
+        let mut buf = BytesMut::new();
+        client.encode(Bytes::from_static(b"hello"), &mut buf).unwrap();
+        assert!(!buf.windows(5).any(|window| window == b"hello"));
+        assert_eq!(server.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"hello")));
+    }
+
+    #[test]
+    fn tampered_frames_are_rejected() {
+        let (mut client, mut server) = pair();
+        let mut buf = BytesMut::new();
+        client.encode(Bytes::from_static(b"hello"), &mut buf).unwrap();
+        let last = buf.len() - 1;
+        buf[last] ^= 1;
+        assert!(matches!(server.decode(&mut buf), Err(AeadError::Authentication)));
+    }
+
+    #[test]
+    fn replayed_frames_are_rejected() {
+        let (mut client, mut server) = pair();
+        let mut buf = BytesMut::new();
+        client.encode(Bytes::from_static(b"once"), &mut buf).unwrap();
+        let replay = buf.clone();
+        server.decode(&mut buf).unwrap().unwrap();
+
+        buf.unsplit(replay);
+        assert!(matches!(server.decode(&mut buf), Err(AeadError::Replayed(0))));
+    }
+
+    #[test]
+    fn reordered_frames_are_rejected() {
+        let (mut client, mut server) = pair();
+        let mut first = BytesMut::new();
+        client.encode(Bytes::from_static(b"first"), &mut first).unwrap();
+        let mut second = BytesMut::new();
+        client.encode(Bytes::from_static(b"second"), &mut second).unwrap();
+
+        assert!(matches!(
+            server.decode(&mut second),
+            Err(AeadError::OutOfOrder { expected: 0, actual: 1 })
+        ));
+    }
+
+    #[test]
+    fn own_frames_do_not_open() {
+        let (mut client, _) = pair();
+        let mut buf = BytesMut::new();
+        client.encode(Bytes::from_static(b"echo"), &mut buf).unwrap();
+        assert!(matches!(client.decode(&mut buf), Err(AeadError::Authentication)));
+    }
+}
+
+#[cfg(all(test, feature = "aesgcm"))]
+mod aes_gcm_tests {
+    use super::*;
+    use crate::codec::LengthCodec;
+    use aes_gcm::{Aes256Gcm, KeyInit};
+
+    fn pair() -> (Aes256GcmCodec<LengthCodec<u32>>, Aes256GcmCodec<LengthCodec<u32>>) {
+        let key = [9u8; 32];
+        let client = AeadCodec::new(
+            LengthCodec::new(),
+            Aes256Gcm::new_from_slice(&key).unwrap(),
+            Side::Client,
+        );
+        let server = AeadCodec::new(
+            LengthCodec::new(),
+            Aes256Gcm::new_from_slice(&key).unwrap(),
+            Side::Server,
+        );
+        (client, server)
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let (mut client, mut server) = pair();
+        let mut buf = BytesMut::new();
+        client.encode(Bytes::from_static(b"hello"), &mut buf).unwrap();
+        client.encode(Bytes::from_static(b"again"), &mut buf).unwrap();
+        assert!(!buf.windows(5).any(|window| window == b"hello"));
+        assert_eq!(server.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"hello")));
+        assert_eq!(server.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"again")));
+    }
+
+    #[test]
+    fn tampered_frames_are_rejected() {
+        let (mut client, mut server) = pair();
+        let mut buf = BytesMut::new();
+        client.encode(Bytes::from_static(b"hello"), &mut buf).unwrap();
+        // Flip a ciphertext bit rather than one in the tag.
+        buf[4 + COUNTER_LEN] ^= 1;
+        assert!(matches!(server.decode(&mut buf), Err(AeadError::Authentication)));
+    }
+}
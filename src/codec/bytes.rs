@@ -1,4 +1,4 @@
-use super::{Decoder, Encoder};
+use super::{Decoder, EncodeBuf, Encoder};
 use bytes::{Bytes, BytesMut};
 use std::convert::Infallible;
 
@@ -13,6 +13,11 @@ impl Encoder for BytesCodec {
         dst.extend_from_slice(&src);
         Ok(())
     }
+
+    fn encode_vectored(&mut self, src: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        dst.push_bytes(src);
+        Ok(())
+    }
 }
 
 impl Decoder for BytesCodec {
@@ -0,0 +1,240 @@
+use super::{Decoder, EncodeBuf, Encoder};
+use bytes::{BufMut, Bytes, BytesMut};
+use std::marker::PhantomData;
+
+#[allow(missing_docs)]
+pub trait Checksum {
+    const LEN: usize;
+
+    fn checksum(data: &[u8]) -> u64;
+}
+
+#[cfg(feature = "crc32c")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Crc32c;
+
+#[cfg(feature = "crc32c")]
+impl Checksum for Crc32c {
+    const LEN: usize = 4;
+
+    fn checksum(data: &[u8]) -> u64 {
+        const CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+        u64::from(CRC.checksum(data))
+    }
+}
+
+#[cfg(feature = "xxhash")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct XxHash64;
+
+#[cfg(feature = "xxhash")]
+impl Checksum for XxHash64 {
+    const LEN: usize = 8;
+
+    fn checksum(data: &[u8]) -> u64 {
+        xxhash_rust::xxh64::xxh64(data, 0)
+    }
+}
+
+#[cfg(feature = "adler32")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Adler32;
+
+#[cfg(feature = "adler32")]
+impl Checksum for Adler32 {
+    const LEN: usize = 4;
+
+    fn checksum(data: &[u8]) -> u64 {
+        u64::from(adler2::adler32_slice(data))
+    }
+}
+
+pub struct ChecksumCodec<C, A> {
+    inner: C,
+    _algorithm: PhantomData<A>,
+}
+
+impl<C, A> ChecksumCodec<C, A> {
+    #[allow(missing_docs)]
+    pub const fn new(inner: C) -> Self {
+        Self {
+            inner,
+            _algorithm: PhantomData,
+        }
+    }
+}
+
+impl<C: Clone, A> Clone for ChecksumCodec<C, A> {
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone())
+    }
+}
+
+impl<C: std::fmt::Debug, A> std::fmt::Debug for ChecksumCodec<C, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChecksumCodec")
+            .field("inner", &self.inner)
+            .field("algorithm", &std::any::type_name::<A>())
+            .finish()
+    }
+}
+
+impl<C: Default, A> Default for ChecksumCodec<C, A> {
+    fn default() -> Self {
+        Self::new(C::default())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChecksumError<E: std::error::Error + 'static> {
+    #[error("checksum mismatch (expected {expected:#x}, got {actual:#x})")]
+    Mismatch { expected: u64, actual: u64 },
+    #[error("frame too short to carry a checksum ({0} bytes)")]
+    Truncated(usize),
+    #[error(transparent)]
+    Inner(#[from] E),
+}
+
+impl<C, A> Encoder for ChecksumCodec<C, A>
+where
+    C: Encoder<Item = Bytes>,
+    A: Checksum,
+{
+    type Error = ChecksumError<C::Error>;
+    type Item = Bytes;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode(append_checksum::<A>(&src), dst)?;
+        Ok(())
+    }
+
+    fn encode_vectored(&mut self, src: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        self.inner.encode_vectored(append_checksum::<A>(&src), dst)?;
+        Ok(())
+    }
+}
+
+impl<C, A> Decoder for ChecksumCodec<C, A>
+where
+    C: Decoder<Item = Bytes>,
+    A: Checksum,
+{
+    type Error = ChecksumError<C::Error>;
+    type Item = Bytes;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode(src)? {
+            Some(frame) => verify::<A, _>(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode_eof(src)? {
+            Some(frame) => verify::<A, _>(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // A frame that fails verification has already been consumed by the inner codec.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        match err {
+            ChecksumError::Mismatch { .. } | ChecksumError::Truncated(_) => true,
+            ChecksumError::Inner(err) => self.inner.is_recoverable(err),
+        }
+    }
+}
+
+fn append_checksum<A: Checksum>(src: &[u8]) -> Bytes {
+    let checksum = A::checksum(src).to_be_bytes();
+    let mut frame = BytesMut::with_capacity(src.len() + A::LEN);
+    frame.put_slice(src);
+    frame.put_slice(&checksum[checksum.len() - A::LEN ..]);
+    frame.freeze()
+}
+
+fn verify<A: Checksum, E: std::error::Error>(mut frame: Bytes) -> Result<Bytes, ChecksumError<E>> {
+    if frame.len() < A::LEN {
+        return Err(ChecksumError::Truncated(frame.len()));
+    }
+    let trailer = frame.split_off(frame.len() - A::LEN);
+    let expected = trailer.iter().fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte));
+    let actual = A::checksum(&frame);
+    if expected != actual {
+        return Err(ChecksumError::Mismatch { expected, actual });
+    }
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::LengthCodec;
+
+    #[derive(Debug)]
+    struct Sum8;
+
+    impl Checksum for Sum8 {
+        const LEN: usize = 1;
+
+        fn checksum(data: &[u8]) -> u64 {
+            u64::from(data.iter().fold(0u8, |acc, &byte| acc.wrapping_add(byte)))
+        }
+    }
+
+    #[test]
+    fn roundtrip_inside_length_frame() {
+        let mut codec = ChecksumCodec::<_, Sum8>::new(LengthCodec::<u16>::new());
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from_static(b"abc"), &mut buf).unwrap();
+        assert_eq!(&buf[..], &[0, 4, b'a', b'b', b'c', 38][..]);
+
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"abc")));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn mismatch_reports_values_and_consumes_frame() {
+        let mut codec = ChecksumCodec::<_, Sum8>::new(LengthCodec::<u16>::new());
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from_static(b"abc"), &mut buf).unwrap();
+        buf[2] = b'x';
+        codec.encode(Bytes::from_static(b"def"), &mut buf).unwrap();
+
+        match codec.decode(&mut buf) {
+            Err(err @ ChecksumError::Mismatch { expected, actual }) => {
+                assert_eq!(expected, 38);
+                assert_eq!(actual, 61);
+                assert!(codec.is_recoverable(&err));
+            },
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"def")));
+    }
+
+    #[cfg(feature = "crc32c")]
+    #[test]
+    fn crc32c_check_value() {
+        assert_eq!(Crc32c::checksum(b"123456789"), 0xe306_9283);
+    }
+
+    #[cfg(feature = "adler32")]
+    #[test]
+    fn adler32_check_value() {
+        assert_eq!(Adler32::checksum(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn composes_with_json_payloads() {
+        use crate::codec::{JsonCodec, PayloadCodec};
+
+        let mut codec = PayloadCodec::new(
+            ChecksumCodec::<_, Sum8>::new(LengthCodec::<u32>::new()),
+            JsonCodec::<Vec<u8>, Vec<u8>>::new(),
+        );
+        let mut buf = BytesMut::new();
+        codec.encode(vec![1, 2, 3], &mut buf).unwrap();
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(vec![1, 2, 3]));
+    }
+}
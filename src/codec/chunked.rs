@@ -0,0 +1,311 @@
+use super::{Decoder, Encoder, http::is_token};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+const MAX_LINE_LEN: usize = 8 * 1024;
+const MAX_TRAILERS: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Chunk {
+    Data(Bytes),
+    // The terminal zero-length chunk together with any trailer fields.
+    End(Vec<(String, Bytes)>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Size,
+    Data(u64),
+    DataCrlf,
+    Trailers,
+    Done,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkedCodec {
+    state: State,
+    trailers: Vec<(String, Bytes)>,
+}
+
+impl ChunkedCodec {
+    #[allow(missing_docs)]
+    pub const fn new() -> Self {
+        Self {
+            state: State::Size,
+            trailers: Vec::new(),
+        }
+    }
+
+    // Whether the terminal chunk and trailers have been decoded. Any bytes remaining in the
+    // buffer after this point belong to whatever follows the body.
+    #[allow(missing_docs)]
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+}
+
+impl Default for ChunkedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChunkedError {
+    #[error("invalid chunk size")]
+    InvalidSize,
+    #[error("chunk data is not followed by CRLF")]
+    MissingCrlf,
+    #[error("invalid trailer field")]
+    InvalidTrailer,
+    #[error("too many trailer fields")]
+    TooManyTrailers,
+    #[error("line exceeds limit")]
+    LineTooLong,
+    #[error("chunked body ended before the terminal chunk")]
+    Truncated,
+}
+
+fn take_line(src: &mut BytesMut) -> Result<Option<BytesMut>, ChunkedError> {
+    match src.windows(2).position(|window| window == b"\r\n") {
+        Some(end) if end > MAX_LINE_LEN => Err(ChunkedError::LineTooLong),
+        Some(end) => {
+            let line = src.split_to(end);
+            src.advance(2);
+            Ok(Some(line))
+        },
+        None if src.len() > MAX_LINE_LEN => Err(ChunkedError::LineTooLong),
+        None => Ok(None),
+    }
+}
+
+fn parse_size(line: &[u8]) -> Result<u64, ChunkedError> {
+    // Only `1*HEXDIG`, so that no sign or padding can make two parsers disagree on the size.
+    let digits = line.iter().take_while(|byte| byte.is_ascii_hexdigit()).count();
+    if digits == 0 || digits > 16 {
+        return Err(ChunkedError::InvalidSize);
+    }
+    // Chunk extensions are permitted but carry nothing we act on. Whitespace may only appear
+    // between the size and an extension.
+    let rest = &line[digits ..];
+    if !rest.is_empty() && rest.trim_ascii_start().first() != Some(&b';') {
+        return Err(ChunkedError::InvalidSize);
+    }
+    let size = std::str::from_utf8(&line[.. digits]).map_err(|_| ChunkedError::InvalidSize)?;
+    u64::from_str_radix(size, 16).map_err(|_| ChunkedError::InvalidSize)
+}
+
+fn parse_trailer(line: BytesMut) -> Result<(String, Bytes), ChunkedError> {
+    let colon = line
+        .iter()
+        .position(|&byte| byte == b':')
+        .ok_or(ChunkedError::InvalidTrailer)?;
+    let name = std::str::from_utf8(&line[.. colon]).map_err(|_| ChunkedError::InvalidTrailer)?;
+    if !is_token(name.as_bytes()) {
+        return Err(ChunkedError::InvalidTrailer);
+    }
+    let value = line[colon + 1 ..].trim_ascii();
+    Ok((name.to_owned(), Bytes::copy_from_slice(value)))
+}
+
+impl Encoder for ChunkedCodec {
+    type Error = ChunkedError;
+    type Item = Chunk;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match item {
+            // A zero-length chunk would terminate the body.
+            Chunk::Data(data) if data.is_empty() => {},
+            Chunk::Data(data) => {
+                let size = format!("{:x}\r\n", data.len());
+                dst.reserve(size.len() + data.len() + 2);
+                dst.put_slice(size.as_bytes());
+                dst.put_slice(&data);
+                dst.put_slice(b"\r\n");
+            },
+            Chunk::End(trailers) => {
+                // Validate everything first so that a bad field leaves nothing half written.
+                let valid = trailers.iter().all(|(name, value)| {
+                    is_token(name.as_bytes()) && !value.iter().any(|&byte| byte == b'\r' || byte == b'\n')
+                });
+                if !valid {
+                    return Err(ChunkedError::InvalidTrailer);
+                }
+                dst.put_slice(b"0\r\n");
+                for (name, value) in trailers {
+                    dst.put_slice(name.as_bytes());
+                    dst.put_slice(b": ");
+                    dst.put_slice(&value);
+                    dst.put_slice(b"\r\n");
+                }
+                dst.put_slice(b"\r\n");
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Decoder for ChunkedCodec {
+    type Error = ChunkedError;
+    type Item = Chunk;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            match self.state {
+                State::Size => match take_line(src)? {
+                    Some(line) => {
+                        self.state = match parse_size(&line)? {
+                            0 => State::Trailers,
+                            size => State::Data(size),
+                        };
+                    },
+                    None => return Ok(None),
+                },
+                State::Data(remaining) => {
+                    if src.is_empty() {
+                        return Ok(None);
+                    }
+                    // Hand out data as it arrives rather than buffering whole chunks.
+                    let len = usize::try_from(remaining).unwrap_or(usize::MAX).min(src.len());
+                    let remaining = remaining - len as u64;
+                    self.state = if remaining == 0 {
+                        State::DataCrlf
+                    } else {
+                        State::Data(remaining)
+                    };
+                    return Ok(Some(Chunk::Data(src.split_to(len).freeze())));
+                },
+                State::DataCrlf => {
+                    if src.len() < 2 {
+                        return Ok(None);
+                    }
+                    if &src[.. 2] != b"\r\n" {
+                        return Err(ChunkedError::MissingCrlf);
+                    }
+                    src.advance(2);
+                    self.state = State::Size;
+                },
+                State::Trailers => match take_line(src)? {
+                    Some(line) if line.is_empty() => {
+                        self.state = State::Done;
+                        return Ok(Some(Chunk::End(std::mem::take(&mut self.trailers))));
+                    },
+                    Some(_) if self.trailers.len() >= MAX_TRAILERS => return Err(ChunkedError::TooManyTrailers),
+                    Some(line) => self.trailers.push(parse_trailer(line)?),
+                    None => return Ok(None),
+                },
+                State::Done => return Ok(None),
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            None if !self.is_done() => Err(ChunkedError::Truncated),
+            item => Ok(item),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(codec: &mut ChunkedCodec, buf: &mut BytesMut) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = codec.decode(buf).unwrap() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut codec = ChunkedCodec::new();
+        let mut buf = BytesMut::new();
+        codec
+            .encode(Chunk::Data(Bytes::from_static(b"Wiki")), &mut buf)
+            .unwrap();
+        codec
+            .encode(Chunk::Data(Bytes::from_static(b"pedia in chunks.")), &mut buf)
+            .unwrap();
+        codec.encode(Chunk::End(vec![]), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"4\r\nWiki\r\n10\r\npedia in chunks.\r\n0\r\n\r\n");
+
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![
+            Chunk::Data(Bytes::from_static(b"Wiki")),
+            Chunk::Data(Bytes::from_static(b"pedia in chunks.")),
+            Chunk::End(vec![]),
+        ]);
+        assert!(codec.is_done());
+    }
+
+    #[test]
+    fn extensions_trailers_and_leftovers() {
+        let mut codec = ChunkedCodec::new();
+        let mut buf = BytesMut::from(&b"5;name=value\r\nhello\r\n0\r\nExpires: never\r\n\r\nNEXT"[..]);
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![
+            Chunk::Data(Bytes::from_static(b"hello")),
+            Chunk::End(vec![("Expires".to_owned(), Bytes::from_static(b"never"))]),
+        ]);
+        assert_eq!(&buf[..], b"NEXT");
+    }
+
+    #[test]
+    fn rejects_trailer_names_that_are_not_tokens() {
+        for name in ["", "X\r\nInjected", "Bad Name", "X:Y"] {
+            let mut buf = BytesMut::new();
+            let trailer = vec![(name.to_owned(), Bytes::from_static(b"v"))];
+            assert!(matches!(
+                ChunkedCodec::new().encode(Chunk::End(trailer), &mut buf),
+                Err(ChunkedError::InvalidTrailer)
+            ));
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn streams_partial_chunks() {
+        let mut codec = ChunkedCodec::new();
+        let mut buf = BytesMut::from(&b"a\r\n01234"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Chunk::Data(Bytes::from_static(b"01234")))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"56789\r\n0\r\n\r\n");
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![
+            Chunk::Data(Bytes::from_static(b"56789")),
+            Chunk::End(vec![]),
+        ]);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for line in [&b"xyz"[..], b"+5", b" 5", b"5 ", b"0x5", b"5\t", b";ext"] {
+            let mut buf = BytesMut::from(line);
+            buf.extend_from_slice(b"\r\n");
+            assert!(matches!(
+                ChunkedCodec::new().decode(&mut buf),
+                Err(ChunkedError::InvalidSize)
+            ));
+        }
+        let mut buf = BytesMut::from(&b"5 ;ext\r\nhello"[..]);
+        assert!(ChunkedCodec::new().decode(&mut buf).unwrap().is_some());
+
+        let mut buf = BytesMut::from(&b"1\r\nabc"[..]);
+        let mut codec = ChunkedCodec::new();
+        codec.decode(&mut buf).unwrap();
+        assert!(matches!(codec.decode(&mut buf), Err(ChunkedError::MissingCrlf)));
+
+        let mut buf = BytesMut::from(&b"0\r\nBad Name: x\r\n\r\n"[..]);
+        assert!(matches!(
+            ChunkedCodec::new().decode(&mut buf),
+            Err(ChunkedError::InvalidTrailer)
+        ));
+
+        let mut buf = BytesMut::from(&b"1\r\na\r\n"[..]);
+        let mut codec = ChunkedCodec::new();
+        codec.decode_eof(&mut buf).unwrap();
+        assert!(matches!(codec.decode_eof(&mut buf), Err(ChunkedError::Truncated)));
+    }
+}
@@ -0,0 +1,333 @@
+use super::{Decoder, EncodeBuf, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::{io, marker::PhantomData};
+
+const FLAG_STORED: u8 = 0;
+const FLAG_COMPRESSED: u8 = 1;
+
+#[allow(missing_docs)]
+pub trait Compression {
+    fn compress(src: &[u8]) -> io::Result<Vec<u8>>;
+
+    // Returns `Ok(None)` when the decompressed payload would be larger than `limit`.
+    fn decompress(src: &[u8], limit: usize) -> io::Result<Option<Vec<u8>>>;
+}
+
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+fn read_to_limit(reader: impl io::Read, limit: usize) -> io::Result<Option<Vec<u8>>> {
+    use std::io::Read;
+
+    let mut dst = Vec::new();
+    let limit = u64::try_from(limit).unwrap_or(u64::MAX);
+    reader.take(limit.saturating_add(1)).read_to_end(&mut dst)?;
+    Ok(if dst.len() as u64 > limit { None } else { Some(dst) })
+}
+
+#[cfg(feature = "deflate")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Deflate;
+
+#[cfg(feature = "deflate")]
+impl Compression for Deflate {
+    fn compress(src: &[u8]) -> io::Result<Vec<u8>> {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(src)?;
+        encoder.finish()
+    }
+
+    fn decompress(src: &[u8], limit: usize) -> io::Result<Option<Vec<u8>>> {
+        read_to_limit(flate2::read::DeflateDecoder::new(src), limit)
+    }
+}
+
+#[cfg(feature = "zstd")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl Compression for Zstd {
+    fn compress(src: &[u8]) -> io::Result<Vec<u8>> {
+        zstd::bulk::compress(src, zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+
+    fn decompress(src: &[u8], limit: usize) -> io::Result<Option<Vec<u8>>> {
+        read_to_limit(zstd::stream::read::Decoder::new(src)?, limit)
+    }
+}
+
+#[cfg(feature = "lz4")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Lz4;
+
+#[cfg(feature = "lz4")]
+impl Compression for Lz4 {
+    fn compress(src: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(lz4_flex::block::compress_prepend_size(src))
+    }
+
+    fn decompress(src: &[u8], limit: usize) -> io::Result<Option<Vec<u8>>> {
+        let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+        // The block format carries the decompressed size up front, so oversized frames are
+        // rejected before anything is allocated.
+        let (size, block) = lz4_flex::block::uncompressed_size(src).map_err(invalid)?;
+        if size > limit {
+            return Ok(None);
+        }
+        lz4_flex::block::decompress(block, size).map(Some).map_err(invalid)
+    }
+}
+
+pub struct CompressionCodec<C, A> {
+    inner: C,
+    threshold: usize,
+    max_decompressed_len: usize,
+    _algorithm: PhantomData<A>,
+}
+
+impl<C, A> CompressionCodec<C, A> {
+    // Payloads shorter than `threshold` are sent uncompressed. Frames which would decompress to
+    // more than `max_decompressed_len` bytes are rejected.
+    #[allow(missing_docs)]
+    pub const fn new(inner: C, threshold: usize, max_decompressed_len: usize) -> Self {
+        Self {
+            inner,
+            threshold,
+            max_decompressed_len,
+            _algorithm: PhantomData,
+        }
+    }
+}
+
+impl<C: Clone, A> Clone for CompressionCodec<C, A> {
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone(), self.threshold, self.max_decompressed_len)
+    }
+}
+
+impl<C: std::fmt::Debug, A> std::fmt::Debug for CompressionCodec<C, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressionCodec")
+            .field("inner", &self.inner)
+            .field("algorithm", &std::any::type_name::<A>())
+            .field("threshold", &self.threshold)
+            .field("max_decompressed_len", &self.max_decompressed_len)
+            .finish()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CompressionError<E: std::error::Error + 'static> {
+    #[error("compression error: {0}")]
+    Compression(#[source] io::Error),
+    #[error("decompressed frame exceeds limit of {0} bytes")]
+    LimitExceeded(usize),
+    #[error("invalid compression flag {0:#x}")]
+    InvalidFlag(u8),
+    #[error("frame is missing the compression flag")]
+    MissingFlag,
+    #[error(transparent)]
+    Inner(#[from] E),
+}
+
+impl<C, A> Encoder for CompressionCodec<C, A>
+where
+    C: Encoder<Item = Bytes>,
+    A: Compression,
+{
+    type Error = CompressionError<C::Error>;
+    type Item = Bytes;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let frame = self.compress(&src)?;
+        self.inner.encode(frame, dst)?;
+        Ok(())
+    }
+
+    fn encode_vectored(&mut self, src: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        let frame = self.compress(&src)?;
+        self.inner.encode_vectored(frame, dst)?;
+        Ok(())
+    }
+}
+
+impl<C, A> CompressionCodec<C, A>
+where
+    C: Encoder,
+    A: Compression,
+{
+    fn compress(&self, src: &[u8]) -> Result<Bytes, CompressionError<C::Error>> {
+        let compressed;
+        let (flag, payload) = if src.len() < self.threshold {
+            (FLAG_STORED, src)
+        } else {
+            compressed = A::compress(src).map_err(CompressionError::Compression)?;
+            (FLAG_COMPRESSED, &compressed[..])
+        };
+
+        let mut frame = BytesMut::with_capacity(1 + payload.len());
+        frame.put_u8(flag);
+        frame.put_slice(payload);
+        Ok(frame.freeze())
+    }
+}
+
+impl<C, A> Decoder for CompressionCodec<C, A>
+where
+    C: Decoder<Item = Bytes>,
+    A: Compression,
+{
+    type Error = CompressionError<C::Error>;
+    type Item = Bytes;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode(src)? {
+            Some(frame) => self.decompress(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.inner.decode_eof(src)? {
+            Some(frame) => self.decompress(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // Our own errors are raised for a frame the inner codec has already consumed.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        match err {
+            CompressionError::Inner(err) => self.inner.is_recoverable(err),
+            _ => true,
+        }
+    }
+}
+
+impl<C, A> CompressionCodec<C, A>
+where
+    C: Decoder,
+    A: Compression,
+{
+    fn decompress(&self, mut frame: Bytes) -> Result<Bytes, CompressionError<C::Error>> {
+        if frame.is_empty() {
+            return Err(CompressionError::MissingFlag);
+        }
+        match frame.get_u8() {
+            FLAG_STORED if frame.len() > self.max_decompressed_len => {
+                Err(CompressionError::LimitExceeded(self.max_decompressed_len))
+            },
+            FLAG_STORED => Ok(frame),
+            FLAG_COMPRESSED => A::decompress(&frame, self.max_decompressed_len)
+                .map_err(CompressionError::Compression)?
+                .map(Bytes::from)
+                .ok_or(CompressionError::LimitExceeded(self.max_decompressed_len)),
+            flag => Err(CompressionError::InvalidFlag(flag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::LengthCodec;
+
+    // Run-length encoding of a single repeated byte; enough to exercise the wrapper.
+    struct Repeat;
+
+    impl Compression for Repeat {
+        fn compress(src: &[u8]) -> io::Result<Vec<u8>> {
+            let mut dst = (src.len() as u32).to_be_bytes().to_vec();
+            dst.push(src.first().copied().unwrap_or(0));
+            Ok(dst)
+        }
+
+        fn decompress(src: &[u8], limit: usize) -> io::Result<Option<Vec<u8>>> {
+            let len = u32::from_be_bytes([src[0], src[1], src[2], src[3]]) as usize;
+            Ok(if len > limit { None } else { Some(vec![src[4]; len]) })
+        }
+    }
+
+    fn codec(threshold: usize, limit: usize) -> CompressionCodec<LengthCodec<u32>, Repeat> {
+        CompressionCodec::new(LengthCodec::new(), threshold, limit)
+    }
+
+    #[test]
+    fn small_frames_are_stored() {
+        let mut codec = codec(16, 1024);
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from_static(b"aaaa"), &mut buf).unwrap();
+        assert_eq!(&buf[..], &[0, 0, 0, 5, FLAG_STORED, b'a', b'a', b'a', b'a'][..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"aaaa")));
+    }
+
+    #[test]
+    fn large_frames_are_compressed() {
+        let mut codec = codec(16, 1024);
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from(vec![b'z'; 100]), &mut buf).unwrap();
+        assert_eq!(&buf[..], &[0, 0, 0, 6, FLAG_COMPRESSED, 0, 0, 0, 100, b'z'][..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from(vec![b'z'; 100])));
+    }
+
+    #[test]
+    fn decompression_bombs_are_rejected() {
+        let mut buf = BytesMut::new();
+        codec(0, usize::MAX)
+            .encode(Bytes::from(vec![0; 4096]), &mut buf)
+            .unwrap();
+        assert!(matches!(
+            codec(0, 1024).decode(&mut buf),
+            Err(CompressionError::LimitExceeded(1024))
+        ));
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn deflate_roundtrip() {
+        roundtrip::<Deflate>();
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_roundtrip() {
+        roundtrip::<Zstd>();
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_roundtrip() {
+        roundtrip::<Lz4>();
+    }
+
+    #[cfg(any(feature = "deflate", feature = "zstd", feature = "lz4"))]
+    fn roundtrip<A: Compression>() {
+        let payload = Bytes::from("compressible ".repeat(256));
+        let mut codec = CompressionCodec::<_, A>::new(LengthCodec::<u32>::new(), 64, payload.len());
+        let mut buf = BytesMut::new();
+        codec.encode(payload.clone(), &mut buf).unwrap();
+        assert!(buf.len() < payload.len());
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(payload.clone()));
+
+        let mut strict = CompressionCodec::<_, A>::new(LengthCodec::<u32>::new(), 64, payload.len() - 1);
+        codec.encode(payload, &mut buf).unwrap();
+        assert!(matches!(
+            strict.decode(&mut buf),
+            Err(CompressionError::LimitExceeded(_))
+        ));
+    }
+
+    #[cfg(all(feature = "json", feature = "deflate"))]
+    #[test]
+    fn compresses_json_inside_length_frame() {
+        use crate::codec::{JsonCodec, PayloadCodec};
+
+        let mut codec = PayloadCodec::new(
+            CompressionCodec::<_, Deflate>::new(LengthCodec::<u32>::new(), 0, 1024),
+            JsonCodec::<String, String>::new(),
+        );
+        let mut buf = BytesMut::new();
+        codec.encode("x".repeat(50), &mut buf).unwrap();
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some("x".repeat(50)));
+    }
+}
@@ -0,0 +1,203 @@
+use bytes::{Buf, Bytes, BytesMut};
+use std::{collections::VecDeque, io::IoSlice};
+
+// Chunks shorter than this are cheaper to copy than to give their own `IoSlice`.
+const COPY_THRESHOLD: usize = 512;
+
+// The destination of `Encoder::encode_vectored`. Bytes written through `buffer_mut` are copied
+// as usual, while chunks handed to `push_bytes` are queued as they are and written out with
+// `poll_write_vectored` alongside them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncodeBuf {
+    // Queued ahead of `tail`, in write order.
+    chunks: VecDeque<Bytes>,
+    chunks_len: usize,
+    tail: BytesMut,
+}
+
+impl EncodeBuf {
+    #[allow(missing_docs)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(missing_docs)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            chunks_len: 0,
+            tail: BytesMut::with_capacity(capacity),
+        }
+    }
+
+    // The buffer that copied bytes are appended to. It always follows any queued chunks.
+    #[allow(missing_docs)]
+    pub fn buffer_mut(&mut self) -> &mut BytesMut {
+        &mut self.tail
+    }
+
+    // Queues `chunk` without copying it, unless it is small enough that copying is cheaper.
+    #[allow(missing_docs)]
+    pub fn push_bytes(&mut self, chunk: Bytes) {
+        if chunk.len() < COPY_THRESHOLD {
+            self.tail.extend_from_slice(&chunk);
+            return;
+        }
+        if !self.tail.is_empty() {
+            let head = self.tail.split().freeze();
+            self.push_chunk(head);
+        }
+        self.push_chunk(chunk);
+    }
+
+    #[allow(missing_docs)]
+    pub fn len(&self) -> usize {
+        self.chunks_len + self.tail.len()
+    }
+
+    #[allow(missing_docs)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Drops everything after the first `len` bytes, such as a frame that failed to encode.
+    #[allow(missing_docs)]
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.chunks_len {
+            self.tail.truncate(len - self.chunks_len);
+            return;
+        }
+        self.tail.clear();
+        while let Some(chunk) = self.chunks.back_mut() {
+            let excess = self.chunks_len - len;
+            if excess == 0 {
+                break;
+            }
+            if excess < chunk.len() {
+                chunk.truncate(chunk.len() - excess);
+                self.chunks_len = len;
+                break;
+            }
+            self.chunks_len -= chunk.len();
+            self.chunks.pop_back();
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.chunks_len = 0;
+        self.tail.clear();
+    }
+
+    fn push_chunk(&mut self, chunk: Bytes) {
+        self.chunks_len += chunk.len();
+        self.chunks.push_back(chunk);
+    }
+}
+
+impl Buf for EncodeBuf {
+    fn remaining(&self) -> usize {
+        self.len()
+    }
+
+    fn chunk(&self) -> &[u8] {
+        match self.chunks.front() {
+            Some(chunk) => chunk,
+            None => &self.tail,
+        }
+    }
+
+    fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+        let chunks = self.chunks.iter().map(|chunk| &chunk[..]);
+        let mut n = 0;
+        for (slot, chunk) in dst.iter_mut().zip(chunks.chain([&self.tail[..]])) {
+            if !chunk.is_empty() {
+                *slot = IoSlice::new(chunk);
+                n += 1;
+            }
+        }
+        n
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        while let Some(chunk) = self.chunks.front_mut() {
+            if cnt < chunk.len() {
+                chunk.advance(cnt);
+                self.chunks_len -= cnt;
+                return;
+            }
+            cnt -= chunk.len();
+            self.chunks_len -= chunk.len();
+            self.chunks.pop_front();
+        }
+        self.tail.advance(cnt);
+    }
+}
+
+impl From<BytesMut> for EncodeBuf {
+    fn from(tail: BytesMut) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            chunks_len: 0,
+            tail,
+        }
+    }
+}
+
+// Flattens any queued chunks into a single buffer.
+impl From<EncodeBuf> for BytesMut {
+    fn from(buf: EncodeBuf) -> Self {
+        if buf.chunks.is_empty() {
+            return buf.tail;
+        }
+        let mut flat = BytesMut::with_capacity(buf.len());
+        for chunk in &buf.chunks {
+            flat.extend_from_slice(chunk);
+        }
+        flat.extend_from_slice(&buf.tail);
+        flat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_write_order() {
+        let large = Bytes::from(vec![b'x'; COPY_THRESHOLD]);
+        let mut buf = EncodeBuf::new();
+        buf.buffer_mut().extend_from_slice(b"head");
+        buf.push_bytes(large.clone());
+        buf.push_bytes(Bytes::from_static(b"small"));
+        assert_eq!(buf.len(), 4 + COPY_THRESHOLD + 5);
+
+        let mut slices = [IoSlice::new(&[]); 4];
+        assert_eq!(buf.chunks_vectored(&mut slices), 3);
+        assert_eq!(&slices[0][..], b"head");
+        assert_eq!(slices[1].as_ptr(), large.as_ptr());
+        assert_eq!(&slices[2][..], b"small");
+
+        buf.advance(6);
+        assert_eq!(buf.chunk().len(), COPY_THRESHOLD - 2);
+        let flat = BytesMut::from(buf);
+        assert_eq!(flat.len(), COPY_THRESHOLD - 2 + 5);
+        assert!(flat.ends_with(b"xxsmall"));
+    }
+
+    #[test]
+    fn truncate_drops_from_the_end() {
+        let mut buf = EncodeBuf::new();
+        buf.buffer_mut().extend_from_slice(b"head");
+        buf.push_bytes(Bytes::from(vec![b'x'; COPY_THRESHOLD]));
+        buf.buffer_mut().extend_from_slice(b"tail");
+
+        buf.truncate(4 + COPY_THRESHOLD + 2);
+        assert_eq!(buf.len(), 4 + COPY_THRESHOLD + 2);
+        buf.truncate(6);
+        assert_eq!(&BytesMut::from(buf.clone())[..], b"headxx");
+        buf.truncate(0);
+        assert!(buf.is_empty());
+    }
+}
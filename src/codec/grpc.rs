@@ -0,0 +1,258 @@
+use super::{Decoder, EncodeBuf, Encoder, PayloadCodec, PayloadError};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+const HEADER_LEN: usize = 5;
+const DEFAULT_MAX_MESSAGE_LEN: usize = 4 << 20;
+// The most buffer space reserved ahead of a message that has not arrived yet, so that a large
+// announced length costs nothing until the peer actually sends it.
+const MAX_RESERVE: usize = 64 * 1024;
+
+// Yields `(compressed, message)` pairs; decompression is left to the caller since the
+// algorithm is negotiated through the `grpc-encoding` header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GrpcCodec {
+    max_message_len: usize,
+}
+
+impl GrpcCodec {
+    // Uses the 4 MiB receive limit common to gRPC implementations.
+    #[allow(missing_docs)]
+    pub const fn new() -> Self {
+        Self::with_max_message_len(DEFAULT_MAX_MESSAGE_LEN)
+    }
+
+    #[allow(missing_docs)]
+    pub const fn with_max_message_len(max_message_len: usize) -> Self {
+        Self { max_message_len }
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_message_len(&self) -> usize {
+        self.max_message_len
+    }
+}
+
+impl Default for GrpcCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GrpcError {
+    #[error("message of {0} bytes exceeds maximum length")]
+    MessageTooLarge(usize),
+    #[error("invalid compressed flag {0}")]
+    InvalidFlag(u8),
+    #[error("compressed message without a decompressor")]
+    Compressed,
+}
+
+impl Encoder for GrpcCodec {
+    type Error = GrpcError;
+    type Item = (bool, Bytes);
+
+    fn encode(&mut self, (compressed, message): Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let len = u32::try_from(message.len()).map_err(|_| GrpcError::MessageTooLarge(message.len()))?;
+        dst.reserve(HEADER_LEN + message.len());
+        dst.put_u8(u8::from(compressed));
+        dst.put_u32(len);
+        dst.put_slice(&message);
+        Ok(())
+    }
+
+    fn encode_vectored(&mut self, (compressed, message): Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        let len = u32::try_from(message.len()).map_err(|_| GrpcError::MessageTooLarge(message.len()))?;
+        let header = dst.buffer_mut();
+        header.put_u8(u8::from(compressed));
+        header.put_u32(len);
+        dst.push_bytes(message);
+        Ok(())
+    }
+}
+
+impl Decoder for GrpcCodec {
+    type Error = GrpcError;
+    type Item = (bool, Bytes);
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let compressed = match src[0] {
+            flag @ (0 | 1) => flag == 1,
+            flag => {
+                src.clear();
+                return Err(GrpcError::InvalidFlag(flag));
+            },
+        };
+        let len = u32::from_be_bytes([src[1], src[2], src[3], src[4]]) as usize;
+        if len > self.max_message_len {
+            // The call is aborted with RESOURCE_EXHAUSTED, so nothing after this is of use.
+            src.clear();
+            return Err(GrpcError::MessageTooLarge(len));
+        }
+        if src.len() < HEADER_LEN + len {
+            src.reserve((HEADER_LEN + len - src.len()).min(MAX_RESERVE));
+            return Ok(None);
+        }
+        src.advance(HEADER_LEN);
+        Ok(Some((compressed, src.split_to(len).freeze())))
+    }
+}
+
+// Runs an item codec such as `JsonCodec` over uncompressed gRPC messages.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GrpcPayloadCodec<C> {
+    inner: PayloadCodec<Uncompressed, C>,
+}
+
+impl<C> GrpcPayloadCodec<C> {
+    #[allow(missing_docs)]
+    pub const fn new(framing: GrpcCodec, payload: C) -> Self {
+        Self {
+            inner: PayloadCodec::new(Uncompressed(framing), payload),
+        }
+    }
+}
+
+impl<C: Encoder> Encoder for GrpcPayloadCodec<C> {
+    type Error = PayloadError<GrpcError, C::Error>;
+    type Item = C::Item;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.inner.encode(item, dst)
+    }
+
+    fn encode_vectored(&mut self, item: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        self.inner.encode_vectored(item, dst)
+    }
+}
+
+impl<C: Decoder> Decoder for GrpcPayloadCodec<C> {
+    type Error = PayloadError<GrpcError, C::Error>;
+    type Item = C::Item;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.inner.decode(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.inner.decode_eof(src)
+    }
+
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        self.inner.is_recoverable(err)
+    }
+}
+
+// Gives `PayloadCodec` plain messages, rejecting compressed ones.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Uncompressed(GrpcCodec);
+
+impl Encoder for Uncompressed {
+    type Error = GrpcError;
+    type Item = Bytes;
+
+    fn encode(&mut self, message: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode((false, message), dst)
+    }
+
+    fn encode_vectored(&mut self, message: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        self.0.encode_vectored((false, message), dst)
+    }
+}
+
+impl Decoder for Uncompressed {
+    type Error = GrpcError;
+    type Item = Bytes;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.0.decode(src)? {
+            Some((false, message)) => Ok(Some(message)),
+            Some((true, _)) => Err(GrpcError::Compressed),
+            None => Ok(None),
+        }
+    }
+
+    // A compressed message has been consumed whole; the other errors leave the stream
+    // unsynchronized.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        matches!(err, GrpcError::Compressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut codec = GrpcCodec::new();
+        let mut buf = BytesMut::new();
+        codec
+            .encode((false, Bytes::from_static(b"\x08\x96\x01")), &mut buf)
+            .unwrap();
+        codec.encode((true, Bytes::new()), &mut buf).unwrap();
+        assert_eq!(&buf[.. 8], b"\x00\x00\x00\x00\x03\x08\x96\x01");
+
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some((false, Bytes::from_static(b"\x08\x96\x01")))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some((true, Bytes::new())));
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn enforces_max_message_len() {
+        let mut codec = GrpcCodec::with_max_message_len(4);
+        let mut buf = BytesMut::from(&b"\x00\x00\x00\x00\x05"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(GrpcError::MessageTooLarge(5))));
+
+        let mut buf = BytesMut::from(&b"\x02\x00\x00\x00\x00"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(GrpcError::InvalidFlag(2))));
+    }
+
+    #[test]
+    fn huge_lengths_do_not_allocate() {
+        let mut codec = GrpcCodec::with_max_message_len(usize::MAX);
+        let mut buf = BytesMut::from(&b"\x00\xff\xff\xff\xff"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.capacity() < 1 << 20);
+    }
+
+    #[test]
+    fn vectored_messages_are_not_copied() {
+        use bytes::Buf;
+        use std::io::IoSlice;
+
+        let message = Bytes::from(vec![0; 1024]);
+        let mut buf = EncodeBuf::new();
+        GrpcCodec::new()
+            .encode_vectored((false, message.clone()), &mut buf)
+            .unwrap();
+        let mut slices = [IoSlice::new(&[]); 2];
+        assert_eq!(buf.chunks_vectored(&mut slices), 2);
+        assert_eq!(&slices[0][..], b"\x00\x00\x00\x04\x00");
+        assert_eq!(slices[1].as_ptr(), message.as_ptr());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn payload_codec() {
+        use crate::codec::JsonCodec;
+
+        let mut codec = GrpcPayloadCodec::new(GrpcCodec::new(), JsonCodec::<u32, u32>::new());
+        let mut buf = BytesMut::new();
+        codec.encode(42, &mut buf).unwrap();
+        assert_eq!(&buf[..], b"\x00\x00\x00\x00\x0242");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(42));
+
+        let mut buf = BytesMut::from(&b"\x01\x00\x00\x00\x0242"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(PayloadError::Frame(GrpcError::Compressed))
+        ));
+    }
+}
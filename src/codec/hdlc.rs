@@ -0,0 +1,265 @@
+use super::{Decoder, DecoderWithSkipAhead, Encoder, SkipAheadHandler};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use crc::{CRC_16_IBM_SDLC, CRC_32_ISO_HDLC, Crc};
+use std::convert::Infallible;
+
+const FLAG: u8 = 0x7e;
+const ESCAPE: u8 = 0x7d;
+const ESCAPE_XOR: u8 = 0x20;
+
+const CRC_16_X25: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_SDLC);
+const CRC_32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fcs {
+    Crc16,
+    Crc32,
+}
+
+impl Fcs {
+    const fn len(self) -> usize {
+        match self {
+            Fcs::Crc16 => 2,
+            Fcs::Crc32 => 4,
+        }
+    }
+
+    fn checksum(self, data: &[u8]) -> u32 {
+        match self {
+            Fcs::Crc16 => u32::from(CRC_16_X25.checksum(data)),
+            Fcs::Crc32 => CRC_32.checksum(data),
+        }
+    }
+
+    // The FCS is transmitted least significant byte first.
+    fn read(self, src: &[u8]) -> u32 {
+        src.iter()
+            .take(self.len())
+            .rev()
+            .fold(0, |acc, &byte| (acc << 8) | u32::from(byte))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HdlcCodec {
+    fcs: Fcs,
+}
+
+impl HdlcCodec {
+    #[allow(missing_docs)]
+    pub const fn new(fcs: Fcs) -> Self {
+        Self { fcs }
+    }
+
+    #[allow(missing_docs)]
+    pub const fn fcs(&self) -> Fcs {
+        self.fcs
+    }
+}
+
+impl Default for HdlcCodec {
+    fn default() -> Self {
+        Self::new(Fcs::Crc16)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HdlcError {
+    #[error("frame check sequence mismatch (expected {expected:#x}, got {actual:#x})")]
+    FcsMismatch { expected: u32, actual: u32 },
+    #[error("frame too short for frame check sequence ({0} bytes)")]
+    Runt(usize),
+    #[error("invalid escape sequence")]
+    InvalidEscape,
+}
+
+fn put_escaped(dst: &mut BytesMut, data: &[u8]) {
+    for &byte in data {
+        if byte == FLAG || byte == ESCAPE {
+            dst.put_u8(ESCAPE);
+            dst.put_u8(byte ^ ESCAPE_XOR);
+        } else {
+            dst.put_u8(byte);
+        }
+    }
+}
+
+fn unescape(src: &[u8]) -> Result<BytesMut, HdlcError> {
+    let mut dst = BytesMut::with_capacity(src.len());
+    let mut iter = src.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == ESCAPE {
+            match iter.next() {
+                Some(&next) => dst.put_u8(next ^ ESCAPE_XOR),
+                None => return Err(HdlcError::InvalidEscape),
+            }
+        } else {
+            dst.put_u8(byte);
+        }
+    }
+    Ok(dst)
+}
+
+impl Encoder for HdlcCodec {
+    type Error = Infallible;
+    type Item = Bytes;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let fcs = self.fcs.checksum(&src).to_le_bytes();
+        // Worst case every byte needs escaping.
+        dst.reserve(2 + 2 * (src.len() + self.fcs.len()));
+        dst.put_u8(FLAG);
+        put_escaped(dst, &src);
+        put_escaped(dst, &fcs[.. self.fcs.len()]);
+        dst.put_u8(FLAG);
+        Ok(())
+    }
+}
+
+impl Decoder for HdlcCodec {
+    type Error = HdlcError;
+    type Item = Bytes;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            // Discard anything preceding the opening flag; we are not synchronized yet.
+            match src.iter().position(|&byte| byte == FLAG) {
+                Some(start) => src.advance(start),
+                None => {
+                    src.clear();
+                    return Ok(None);
+                },
+            }
+
+            // Collapse runs of flags (idle fill and shared opening/closing flags).
+            let idle = src.iter().take_while(|&&byte| byte == FLAG).count();
+            src.advance(idle - 1);
+
+            let len = match src[1 ..].iter().position(|&byte| byte == FLAG) {
+                Some(len) => len,
+                None => return Ok(None),
+            };
+
+            // The closing flag is left in place to serve as the next opening flag.
+            let frame = src.split_to(1 + len);
+            let mut frame = unescape(&frame[1 ..])?;
+
+            if frame.len() < self.fcs.len() {
+                if frame.is_empty() {
+                    continue;
+                }
+                return Err(HdlcError::Runt(frame.len()));
+            }
+
+            let fcs = frame.split_off(frame.len() - self.fcs.len());
+            let expected = self.fcs.read(&fcs);
+            let actual = self.fcs.checksum(&frame);
+            if expected != actual {
+                log::trace!("Discarding frame with bad FCS");
+                return Err(HdlcError::FcsMismatch { expected, actual });
+            }
+
+            return Ok(Some(frame.freeze()));
+        }
+    }
+
+    // Every error is raised after the offending frame has been taken off the buffer, so the
+    // next frame can be decoded as usual.
+    fn is_recoverable(&self, _err: &Self::Error) -> bool {
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct HdlcSkipAhead;
+
+impl SkipAheadHandler for HdlcSkipAhead {
+    fn continue_skipping(self, src: &[u8]) -> anyhow::Result<(usize, Option<Self>)> {
+        Ok(match src.iter().position(|&byte| byte == FLAG) {
+            Some(pos) => (pos, None),
+            None => (src.len(), Some(self)),
+        })
+    }
+}
+
+impl DecoderWithSkipAhead for HdlcCodec {
+    type Handler = HdlcSkipAhead;
+
+    fn prepare_skip_ahead(&mut self, src: &mut BytesMut) -> Self::Handler {
+        // The buffer holds a single unterminated frame; drop it and hunt for the next flag.
+        src.clear();
+        HdlcSkipAhead
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(fcs: Fcs) {
+        let mut codec = HdlcCodec::new(fcs);
+        let mut buf = BytesMut::new();
+        let payload = Bytes::from_static(&[0x01, FLAG, 0x02, ESCAPE, 0x03]);
+
+        codec.encode(payload.clone(), &mut buf).unwrap();
+        assert!(!buf[1 .. buf.len() - 1].contains(&FLAG));
+
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(payload));
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn roundtrip_crc16() {
+        roundtrip(Fcs::Crc16);
+    }
+
+    #[test]
+    fn roundtrip_crc32() {
+        roundtrip(Fcs::Crc32);
+    }
+
+    #[test]
+    fn crc16_matches_x25_check_value() {
+        assert_eq!(Fcs::Crc16.checksum(b"123456789"), 0x906e);
+    }
+
+    #[test]
+    fn partial_frame_waits_for_closing_flag() {
+        let mut codec = HdlcCodec::default();
+        let mut full = BytesMut::new();
+        codec.encode(Bytes::from_static(b"hello"), &mut full).unwrap();
+
+        let mut buf = full.split_to(4);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.unsplit(full);
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"hello")));
+    }
+
+    #[test]
+    fn bad_fcs_is_discarded_and_stream_continues() {
+        let mut codec = HdlcCodec::new(Fcs::Crc32);
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from_static(b"first"), &mut buf).unwrap();
+        buf[2] ^= 0xff;
+        codec.encode(Bytes::from_static(b"second"), &mut buf).unwrap();
+
+        assert!(matches!(codec.decode(&mut buf), Err(HdlcError::FcsMismatch { .. })));
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"second")));
+    }
+
+    #[test]
+    fn shared_flags_and_leading_garbage() {
+        let mut codec = HdlcCodec::default();
+        let mut a = BytesMut::new();
+        codec.encode(Bytes::from_static(b"a"), &mut a).unwrap();
+        let mut b = BytesMut::new();
+        codec.encode(Bytes::from_static(b"b"), &mut b).unwrap();
+
+        let mut buf = BytesMut::from(&b"garbage"[..]);
+        buf.extend_from_slice(&a);
+        buf.extend_from_slice(&b[1 ..]);
+
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"a")));
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"b")));
+    }
+}
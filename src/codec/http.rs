@@ -0,0 +1,426 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::marker::PhantomData;
+
+const MAX_HEADERS: usize = 100;
+const MAX_HEAD_LEN: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
+
+impl HttpVersion {
+    fn parse(src: &[u8]) -> Result<Self, HttpError> {
+        match src {
+            b"HTTP/1.0" => Ok(HttpVersion::Http10),
+            b"HTTP/1.1" => Ok(HttpVersion::Http11),
+            _ => Err(HttpError::UnsupportedVersion),
+        }
+    }
+
+    const fn as_bytes(self) -> &'static [u8] {
+        match self {
+            HttpVersion::Http10 => b"HTTP/1.0",
+            HttpVersion::Http11 => b"HTTP/1.1",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestHead {
+    pub method: String,
+    pub target: String,
+    pub version: HttpVersion,
+    pub headers: Vec<(String, Bytes)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResponseHead {
+    pub version: HttpVersion,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, Bytes)>,
+}
+
+// A start line followed by header fields; implemented by `RequestHead` and `ResponseHead`.
+#[allow(missing_docs)]
+pub trait HttpHead: Sized {
+    fn parse(start_line: &[u8], headers: Vec<(String, Bytes)>) -> Result<Self, HttpError>;
+
+    fn encode_start_line(&self, dst: &mut BytesMut) -> Result<(), HttpError>;
+
+    fn headers(&self) -> &[(String, Bytes)];
+
+    // Returns the first header with the given name, compared case-insensitively.
+    fn header(&self, name: &str) -> Option<&Bytes> {
+        self.headers()
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+}
+
+pub(super) fn is_token(src: &[u8]) -> bool {
+    !src.is_empty()
+        && src
+            .iter()
+            .all(|&byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+fn is_field_value(src: &[u8]) -> bool {
+    src.iter().all(|&byte| byte == b'\t' || (byte >= b' ' && byte != 0x7f))
+}
+
+impl HttpHead for RequestHead {
+    fn parse(start_line: &[u8], headers: Vec<(String, Bytes)>) -> Result<Self, HttpError> {
+        let mut parts = start_line.split(|&byte| byte == b' ');
+        let (Some(method), Some(target), Some(version), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(HttpError::InvalidStartLine);
+        };
+        if !is_token(method) || target.is_empty() || !target.iter().all(u8::is_ascii_graphic) {
+            return Err(HttpError::InvalidStartLine);
+        }
+        Ok(RequestHead {
+            // Both were checked to be ASCII above.
+            method: String::from_utf8_lossy(method).into_owned(),
+            target: String::from_utf8_lossy(target).into_owned(),
+            version: HttpVersion::parse(version)?,
+            headers,
+        })
+    }
+
+    fn encode_start_line(&self, dst: &mut BytesMut) -> Result<(), HttpError> {
+        if !is_token(self.method.as_bytes())
+            || self.target.is_empty()
+            || !self.target.bytes().all(|byte| byte.is_ascii_graphic())
+        {
+            return Err(HttpError::InvalidStartLine);
+        }
+        dst.put_slice(self.method.as_bytes());
+        dst.put_u8(b' ');
+        dst.put_slice(self.target.as_bytes());
+        dst.put_u8(b' ');
+        dst.put_slice(self.version.as_bytes());
+        Ok(())
+    }
+
+    fn headers(&self) -> &[(String, Bytes)] {
+        &self.headers
+    }
+}
+
+impl HttpHead for ResponseHead {
+    fn parse(start_line: &[u8], headers: Vec<(String, Bytes)>) -> Result<Self, HttpError> {
+        let mut parts = start_line.splitn(3, |&byte| byte == b' ');
+        let (Some(version), Some(status)) = (parts.next(), parts.next()) else {
+            return Err(HttpError::InvalidStartLine);
+        };
+        let reason = parts.next().unwrap_or_default();
+        if status.len() != 3 || !status.iter().all(u8::is_ascii_digit) || !is_field_value(reason) {
+            return Err(HttpError::InvalidStartLine);
+        }
+        Ok(ResponseHead {
+            version: HttpVersion::parse(version)?,
+            status: status.iter().fold(0, |acc, &digit| acc * 10 + u16::from(digit - b'0')),
+            reason: String::from_utf8_lossy(reason).into_owned(),
+            headers,
+        })
+    }
+
+    fn encode_start_line(&self, dst: &mut BytesMut) -> Result<(), HttpError> {
+        if !(100 ..= 999).contains(&self.status) || !is_field_value(self.reason.as_bytes()) {
+            return Err(HttpError::InvalidStartLine);
+        }
+        dst.put_slice(self.version.as_bytes());
+        dst.put_slice(format!(" {} ", self.status).as_bytes());
+        dst.put_slice(self.reason.as_bytes());
+        Ok(())
+    }
+
+    fn headers(&self) -> &[(String, Bytes)] {
+        &self.headers
+    }
+}
+
+// Decodes a single head at a time. Whatever follows the blank line is left in the buffer, so
+// after a head has been yielded the caller can take `Framed::into_parts` and continue with a
+// body codec over `read_buf`.
+pub struct HttpHeadCodec<H> {
+    max_headers: usize,
+    max_head_len: usize,
+    // How far the buffer has already been searched for the end of the head.
+    scanned: usize,
+    _head: PhantomData<fn() -> H>,
+}
+
+pub type RequestHeadCodec = HttpHeadCodec<RequestHead>;
+pub type ResponseHeadCodec = HttpHeadCodec<ResponseHead>;
+
+impl<H> HttpHeadCodec<H> {
+    #[allow(missing_docs)]
+    pub const fn new() -> Self {
+        Self::with_limits(MAX_HEADERS, MAX_HEAD_LEN)
+    }
+
+    #[allow(missing_docs)]
+    pub const fn with_limits(max_headers: usize, max_head_len: usize) -> Self {
+        Self {
+            max_headers,
+            max_head_len,
+            scanned: 0,
+            _head: PhantomData,
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_headers(&self) -> usize {
+        self.max_headers
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_head_len(&self) -> usize {
+        self.max_head_len
+    }
+}
+
+impl<H> Clone for HttpHeadCodec<H> {
+    fn clone(&self) -> Self {
+        Self {
+            max_headers: self.max_headers,
+            max_head_len: self.max_head_len,
+            scanned: self.scanned,
+            _head: PhantomData,
+        }
+    }
+}
+
+impl<H> std::fmt::Debug for HttpHeadCodec<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpHeadCodec")
+            .field("head", &std::any::type_name::<H>())
+            .field("max_headers", &self.max_headers)
+            .field("max_head_len", &self.max_head_len)
+            .finish()
+    }
+}
+
+impl<H> Default for HttpHeadCodec<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error("invalid start line")]
+    InvalidStartLine,
+    #[error("unsupported HTTP version")]
+    UnsupportedVersion,
+    #[error("invalid header field")]
+    InvalidHeader,
+    #[error("too many header fields")]
+    TooManyHeaders,
+    #[error("message head exceeds size limit")]
+    HeadTooLarge,
+}
+
+fn parse_header(line: &[u8]) -> Result<(String, Bytes), HttpError> {
+    let colon = line
+        .iter()
+        .position(|&byte| byte == b':')
+        .ok_or(HttpError::InvalidHeader)?;
+    // This also rejects obsolete line folding, whose continuation lines start with whitespace.
+    let (name, value) = (&line[.. colon], line[colon + 1 ..].trim_ascii());
+    if !is_token(name) || !is_field_value(value) {
+        return Err(HttpError::InvalidHeader);
+    }
+    Ok((
+        String::from_utf8_lossy(name).into_owned(),
+        Bytes::copy_from_slice(value),
+    ))
+}
+
+// Finds the empty line ending a head, searching from `start`. Lines may end in a bare LF, as
+// they may in `parse`. Returns the length of the head without its final line ending, and with
+// the empty line.
+fn find_head_end(src: &[u8], start: usize) -> Option<(usize, usize)> {
+    (start .. src.len())
+        .filter(|&i| src[i] == b'\n')
+        .find_map(|i| match &src[i + 1 ..] {
+            [b'\n', ..] => Some((i, i + 2)),
+            [b'\r', b'\n', ..] => Some((i, i + 3)),
+            _ => None,
+        })
+}
+
+impl<H: HttpHead> HttpHeadCodec<H> {
+    fn parse(&self, head: &[u8]) -> Result<H, HttpError> {
+        let mut lines = head
+            .split(|&byte| byte == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+        let start_line = lines.next().unwrap_or_default();
+        let mut headers = Vec::new();
+        for line in lines {
+            if headers.len() == self.max_headers {
+                return Err(HttpError::TooManyHeaders);
+            }
+            headers.push(parse_header(line)?);
+        }
+        H::parse(start_line, headers)
+    }
+}
+
+impl<H: HttpHead> Encoder for HttpHeadCodec<H> {
+    type Error = HttpError;
+    type Item = H;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut head = BytesMut::new();
+        item.encode_start_line(&mut head)?;
+        head.put_slice(b"\r\n");
+        for (name, value) in item.headers() {
+            if !is_token(name.as_bytes()) || !is_field_value(value) {
+                return Err(HttpError::InvalidHeader);
+            }
+            head.put_slice(name.as_bytes());
+            head.put_slice(b": ");
+            head.put_slice(value);
+            head.put_slice(b"\r\n");
+        }
+        head.put_slice(b"\r\n");
+        dst.extend_from_slice(&head);
+        Ok(())
+    }
+}
+
+impl<H: HttpHead> Decoder for HttpHeadCodec<H> {
+    type Error = HttpError;
+    type Item = H;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // Empty lines ahead of a head are ignored, as RFC 9112 recommends.
+        while src.starts_with(b"\r\n") || src.starts_with(b"\n") {
+            src.advance(if src[0] == b'\r' { 2 } else { 1 });
+        }
+
+        // A buffer shorter than what was scanned before has been replaced, so start over.
+        let start = match src.len() < self.scanned {
+            true => 0,
+            false => self.scanned.saturating_sub(2),
+        };
+        let (end, head_len) = match find_head_end(src, start) {
+            Some((end, head_len)) if end <= self.max_head_len => (end, head_len),
+            Some(_) => {
+                self.scanned = 0;
+                src.clear();
+                return Err(HttpError::HeadTooLarge);
+            },
+            None if src.len() > self.max_head_len + 3 => {
+                self.scanned = 0;
+                src.clear();
+                return Err(HttpError::HeadTooLarge);
+            },
+            None => {
+                self.scanned = src.len();
+                return Ok(None);
+            },
+        };
+
+        self.scanned = 0;
+        let head = src.split_to(head_len);
+        self.parse(&head[.. end]).map(Some).inspect_err(|_| {
+            // A malformed head leaves no way to tell where its body ends.
+            src.clear();
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_roundtrip() {
+        let mut codec = RequestHeadCodec::new();
+        let head = RequestHead {
+            method: "GET".into(),
+            target: "/index.html".into(),
+            version: HttpVersion::Http11,
+            headers: vec![("Host".into(), Bytes::from_static(b"example.com"))],
+        };
+        let mut buf = BytesMut::new();
+        codec.encode(head.clone(), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(head));
+    }
+
+    #[test]
+    fn response_leaves_body_buffered() {
+        let mut codec = ResponseHeadCodec::new();
+        let mut buf = BytesMut::from(&b"HTTP/1.1 200 OK\r\nContent-Length:  5 \r\n\r\nhello"[..]);
+        let head = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(head.status, 200);
+        assert_eq!(head.reason, "OK");
+        assert_eq!(head.header("content-length"), Some(&Bytes::from_static(b"5")));
+        assert_eq!(&buf[..], b"hello");
+    }
+
+    #[test]
+    fn partial_input() {
+        let mut codec = RequestHeadCodec::new();
+        let mut buf = BytesMut::from(&b"\r\nGET / HTTP/1.0\r\nHost: a\r"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"\n\r\n");
+        let head = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(head.version, HttpVersion::Http10);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn accepts_bare_lf_line_endings() {
+        let mut codec = RequestHeadCodec::new();
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\nHost: a\r\nAccept: */*\n\nbody"[..]);
+        let head = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(head.header("host"), Some(&Bytes::from_static(b"a")));
+        assert_eq!(head.header("accept"), Some(&Bytes::from_static(b"*/*")));
+        assert_eq!(&buf[..], b"body");
+    }
+
+    #[test]
+    fn replaced_buffer_is_rescanned() {
+        let mut codec = RequestHeadCodec::new();
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\nHost: a-rather-long-name\r\n"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\n\r\n"[..]);
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let mut codec = RequestHeadCodec::with_limits(1, 64);
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(HttpError::TooManyHeaders)));
+
+        let mut buf = BytesMut::from(&[b'a'; 128][..]);
+        assert!(matches!(codec.decode(&mut buf), Err(HttpError::HeadTooLarge)));
+    }
+
+    #[test]
+    fn rejects_malformed_heads() {
+        let mut codec = RequestHeadCodec::new();
+        for input in [
+            &b"GET /\r\n\r\n"[..],
+            b"GET / HTTP/2.0\r\n\r\n",
+            b"GET / HTTP/1.1\r\nBad Name: x\r\n\r\n",
+            b"GET / HTTP/1.1\r\nA: 1\r\n folded\r\n\r\n",
+        ] {
+            let mut buf = BytesMut::from(input);
+            assert!(codec.decode(&mut buf).is_err());
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+const HEADER_LEN: usize = 9;
+const DEFAULT_MAX_FRAME_SIZE: u32 = 1 << 14;
+const MAX_MAX_FRAME_SIZE: u32 = (1 << 24) - 1;
+const STREAM_ID_MASK: u32 = (1 << 31) - 1;
+// The most buffer space reserved ahead of a frame that has not arrived yet, so that a large
+// announced length costs nothing until the peer actually sends it.
+const MAX_RESERVE: usize = 64 * 1024;
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Http2Priority {
+    pub exclusive: bool,
+    pub dependency: u32,
+    pub weight: u8,
+}
+
+// Padding is stripped on decode and never added on encode. Header blocks are passed through
+// as opaque fragments, leaving HPACK to the caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Http2Frame {
+    Data {
+        stream_id: u32,
+        end_stream: bool,
+        data: Bytes,
+    },
+    Headers {
+        stream_id: u32,
+        end_stream: bool,
+        end_headers: bool,
+        priority: Option<Http2Priority>,
+        fragment: Bytes,
+    },
+    RstStream {
+        stream_id: u32,
+        error_code: u32,
+    },
+    Settings {
+        ack: bool,
+        settings: Vec<(u16, u32)>,
+    },
+    Ping {
+        ack: bool,
+        data: [u8; 8],
+    },
+    GoAway {
+        last_stream_id: u32,
+        error_code: u32,
+        debug_data: Bytes,
+    },
+    WindowUpdate {
+        stream_id: u32,
+        increment: u32,
+    },
+    // PRIORITY, PUSH_PROMISE, CONTINUATION and extension frames, passed through unparsed.
+    Other {
+        kind: u8,
+        flags: u8,
+        stream_id: u32,
+        payload: Bytes,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Http2Codec {
+    max_recv_frame_size: u32,
+    max_send_frame_size: u32,
+    expect_preface: bool,
+    send_preface: bool,
+}
+
+impl Http2Codec {
+    #[allow(missing_docs)]
+    pub const fn new() -> Self {
+        Self {
+            max_recv_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_send_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            expect_preface: false,
+            send_preface: false,
+        }
+    }
+
+    // Writes the client connection preface ahead of the first encoded frame.
+    #[allow(missing_docs)]
+    pub const fn client() -> Self {
+        Self {
+            send_preface: true,
+            ..Self::new()
+        }
+    }
+
+    // Requires the client connection preface before the first decoded frame.
+    #[allow(missing_docs)]
+    pub const fn server() -> Self {
+        Self {
+            expect_preface: true,
+            ..Self::new()
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_recv_frame_size(&self) -> u32 {
+        self.max_recv_frame_size
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_send_frame_size(&self) -> u32 {
+        self.max_send_frame_size
+    }
+
+    // Applies the `SETTINGS_MAX_FRAME_SIZE` we advertised, which bounds the frames we decode.
+    #[allow(missing_docs)]
+    pub fn set_max_recv_frame_size(&mut self, size: u32) -> Result<(), Http2Error> {
+        self.max_recv_frame_size = validate_max_frame_size(size)?;
+        Ok(())
+    }
+
+    // Applies the `SETTINGS_MAX_FRAME_SIZE` the peer advertised, which bounds the frames we
+    // encode.
+    #[allow(missing_docs)]
+    pub fn set_max_send_frame_size(&mut self, size: u32) -> Result<(), Http2Error> {
+        self.max_send_frame_size = validate_max_frame_size(size)?;
+        Ok(())
+    }
+}
+
+fn validate_max_frame_size(size: u32) -> Result<u32, Http2Error> {
+    if (DEFAULT_MAX_FRAME_SIZE ..= MAX_MAX_FRAME_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(Http2Error::InvalidMaxFrameSize(size))
+    }
+}
+
+impl Default for Http2Codec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Http2Error {
+    #[error("invalid client connection preface")]
+    InvalidPreface,
+    #[error("frame of {0} bytes exceeds SETTINGS_MAX_FRAME_SIZE")]
+    FrameTooLarge(usize),
+    #[error("invalid SETTINGS_MAX_FRAME_SIZE {0}")]
+    InvalidMaxFrameSize(u32),
+    #[error("invalid frame: {0}")]
+    InvalidFrame(&'static str),
+}
+
+fn strip_padding(flags: u8, mut payload: Bytes) -> Result<Bytes, Http2Error> {
+    if flags & PADDED == 0 {
+        return Ok(payload);
+    }
+    if payload.is_empty() {
+        return Err(Http2Error::InvalidFrame("missing pad length"));
+    }
+    let pad_len = usize::from(payload.get_u8());
+    if pad_len > payload.len() {
+        return Err(Http2Error::InvalidFrame("padding exceeds payload"));
+    }
+    payload.truncate(payload.len() - pad_len);
+    Ok(payload)
+}
+
+fn parse_frame(kind: u8, flags: u8, stream_id: u32, payload: Bytes) -> Result<Http2Frame, Http2Error> {
+    let on_stream = |frame| match stream_id {
+        0 => Err(Http2Error::InvalidFrame("frame requires a stream")),
+        _ => Ok(frame),
+    };
+    let on_connection = |frame| match stream_id {
+        0 => Ok(frame),
+        _ => Err(Http2Error::InvalidFrame("frame must be sent on stream 0")),
+    };
+
+    match kind {
+        DATA => on_stream(Http2Frame::Data {
+            stream_id,
+            end_stream: flags & END_STREAM != 0,
+            data: strip_padding(flags, payload)?,
+        }),
+        HEADERS => {
+            let mut fragment = strip_padding(flags, payload)?;
+            let priority = if flags & PRIORITY != 0 {
+                if fragment.len() < 5 {
+                    return Err(Http2Error::InvalidFrame("truncated priority"));
+                }
+                let dependency = fragment.get_u32();
+                Some(Http2Priority {
+                    exclusive: dependency >> 31 != 0,
+                    dependency: dependency & STREAM_ID_MASK,
+                    weight: fragment.get_u8(),
+                })
+            } else {
+                None
+            };
+            on_stream(Http2Frame::Headers {
+                stream_id,
+                end_stream: flags & END_STREAM != 0,
+                end_headers: flags & END_HEADERS != 0,
+                priority,
+                fragment,
+            })
+        },
+        RST_STREAM if payload.len() != 4 => Err(Http2Error::InvalidFrame("RST_STREAM must be 4 bytes")),
+        RST_STREAM => on_stream(Http2Frame::RstStream {
+            stream_id,
+            error_code: payload.clone().get_u32(),
+        }),
+        SETTINGS if !payload.len().is_multiple_of(6) => {
+            Err(Http2Error::InvalidFrame("SETTINGS length not a multiple of 6"))
+        },
+        SETTINGS if flags & ACK != 0 && !payload.is_empty() => {
+            Err(Http2Error::InvalidFrame("SETTINGS acknowledgement with payload"))
+        },
+        SETTINGS => on_connection(Http2Frame::Settings {
+            ack: flags & ACK != 0,
+            settings: payload
+                .chunks(6)
+                .map(|mut setting| (setting.get_u16(), setting.get_u32()))
+                .collect(),
+        }),
+        PING if payload.len() != 8 => Err(Http2Error::InvalidFrame("PING must be 8 bytes")),
+        PING => {
+            let mut data = [0u8; 8];
+            data.copy_from_slice(&payload);
+            on_connection(Http2Frame::Ping {
+                ack: flags & ACK != 0,
+                data,
+            })
+        },
+        GOAWAY if payload.len() < 8 => Err(Http2Error::InvalidFrame("truncated GOAWAY")),
+        GOAWAY => {
+            let mut payload = payload;
+            on_connection(Http2Frame::GoAway {
+                last_stream_id: payload.get_u32() & STREAM_ID_MASK,
+                error_code: payload.get_u32(),
+                debug_data: payload,
+            })
+        },
+        WINDOW_UPDATE if payload.len() != 4 => Err(Http2Error::InvalidFrame("WINDOW_UPDATE must be 4 bytes")),
+        WINDOW_UPDATE => match payload.clone().get_u32() & STREAM_ID_MASK {
+            0 => Err(Http2Error::InvalidFrame("zero window increment")),
+            increment => Ok(Http2Frame::WindowUpdate { stream_id, increment }),
+        },
+        kind => Ok(Http2Frame::Other {
+            kind,
+            flags,
+            stream_id,
+            payload,
+        }),
+    }
+}
+
+impl Encoder for Http2Codec {
+    type Error = Http2Error;
+    type Item = Http2Frame;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut payload = BytesMut::new();
+        let (kind, flags, stream_id) = match item {
+            Http2Frame::Data {
+                stream_id,
+                end_stream,
+                data,
+            } => {
+                payload.put_slice(&data);
+                (DATA, if end_stream { END_STREAM } else { 0 }, stream_id)
+            },
+            Http2Frame::Headers {
+                stream_id,
+                end_stream,
+                end_headers,
+                priority,
+                fragment,
+            } => {
+                let mut flags = 0;
+                if end_stream {
+                    flags |= END_STREAM;
+                }
+                if end_headers {
+                    flags |= END_HEADERS;
+                }
+                if let Some(priority) = priority {
+                    flags |= PRIORITY;
+                    payload.put_u32(u32::from(priority.exclusive) << 31 | priority.dependency & STREAM_ID_MASK);
+                    payload.put_u8(priority.weight);
+                }
+                payload.put_slice(&fragment);
+                (HEADERS, flags, stream_id)
+            },
+            Http2Frame::RstStream { stream_id, error_code } => {
+                payload.put_u32(error_code);
+                (RST_STREAM, 0, stream_id)
+            },
+            Http2Frame::Settings { ack, settings } => {
+                for (id, value) in settings {
+                    payload.put_u16(id);
+                    payload.put_u32(value);
+                }
+                (SETTINGS, if ack { ACK } else { 0 }, 0)
+            },
+            Http2Frame::Ping { ack, data } => {
+                payload.put_slice(&data);
+                (PING, if ack { ACK } else { 0 }, 0)
+            },
+            Http2Frame::GoAway {
+                last_stream_id,
+                error_code,
+                debug_data,
+            } => {
+                payload.put_u32(last_stream_id & STREAM_ID_MASK);
+                payload.put_u32(error_code);
+                payload.put_slice(&debug_data);
+                (GOAWAY, 0, 0)
+            },
+            Http2Frame::WindowUpdate { stream_id, increment } => {
+                payload.put_u32(increment & STREAM_ID_MASK);
+                (WINDOW_UPDATE, 0, stream_id)
+            },
+            Http2Frame::Other {
+                kind,
+                flags,
+                stream_id,
+                payload: data,
+            } => {
+                payload.put_slice(&data);
+                (kind, flags, stream_id)
+            },
+        };
+
+        if payload.len() > self.max_send_frame_size as usize {
+            return Err(Http2Error::FrameTooLarge(payload.len()));
+        }
+        if self.send_preface {
+            dst.put_slice(PREFACE);
+            self.send_preface = false;
+        }
+        dst.reserve(HEADER_LEN + payload.len());
+        dst.put_uint(payload.len() as u64, 3);
+        dst.put_u8(kind);
+        dst.put_u8(flags);
+        dst.put_u32(stream_id & STREAM_ID_MASK);
+        dst.put_slice(&payload);
+        Ok(())
+    }
+}
+
+impl Decoder for Http2Codec {
+    type Error = Http2Error;
+    type Item = Http2Frame;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.expect_preface {
+            let len = src.len().min(PREFACE.len());
+            if src[.. len] != PREFACE[.. len] {
+                src.clear();
+                return Err(Http2Error::InvalidPreface);
+            }
+            if len < PREFACE.len() {
+                return Ok(None);
+            }
+            src.advance(PREFACE.len());
+            self.expect_preface = false;
+        }
+
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let len = (usize::from(src[0]) << 16) | (usize::from(src[1]) << 8) | usize::from(src[2]);
+        if len > self.max_recv_frame_size as usize {
+            // Frame size errors are connection errors, so there is nothing to resynchronize.
+            src.clear();
+            return Err(Http2Error::FrameTooLarge(len));
+        }
+        if src.len() < HEADER_LEN + len {
+            src.reserve((HEADER_LEN + len - src.len()).min(MAX_RESERVE));
+            return Ok(None);
+        }
+
+        let mut header = src.split_to(HEADER_LEN);
+        let payload = src.split_to(len).freeze();
+        header.advance(3);
+        let (kind, flags, stream_id) = (header.get_u8(), header.get_u8(), header.get_u32() & STREAM_ID_MASK);
+        parse_frame(kind, flags, stream_id, payload).map(Some)
+    }
+
+    // An invalid frame has been consumed whole, so the next one can still be read; a bad
+    // preface or an oversized frame leaves nothing to resynchronize with.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        matches!(err, Http2Error::InvalidFrame(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let frames = vec![
+            Http2Frame::Settings {
+                ack: false,
+                settings: vec![(0x3, 100), (0x4, 65535)],
+            },
+            Http2Frame::Headers {
+                stream_id: 1,
+                end_stream: false,
+                end_headers: true,
+                priority: Some(Http2Priority {
+                    exclusive: true,
+                    dependency: 0,
+                    weight: 15,
+                }),
+                fragment: Bytes::from_static(b"\x82\x86\x84"),
+            },
+            Http2Frame::Data {
+                stream_id: 1,
+                end_stream: true,
+                data: Bytes::from_static(b"hello"),
+            },
+            Http2Frame::WindowUpdate {
+                stream_id: 0,
+                increment: 1024,
+            },
+            Http2Frame::Ping {
+                ack: true,
+                data: *b"pingpong",
+            },
+            Http2Frame::RstStream {
+                stream_id: 3,
+                error_code: 0x8,
+            },
+            Http2Frame::GoAway {
+                last_stream_id: 1,
+                error_code: 0,
+                debug_data: Bytes::from_static(b"bye"),
+            },
+            Http2Frame::Other {
+                kind: 0x9,
+                flags: END_HEADERS,
+                stream_id: 1,
+                payload: Bytes::from_static(b"\x88"),
+            },
+        ];
+
+        let mut client = Http2Codec::client();
+        let mut server = Http2Codec::server();
+        let mut buf = BytesMut::new();
+        for frame in frames.clone() {
+            client.encode(frame, &mut buf).unwrap();
+        }
+        assert!(buf.starts_with(PREFACE));
+        for frame in frames {
+            assert_eq!(server.decode(&mut buf).unwrap(), Some(frame));
+        }
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn strips_padding() {
+        let mut codec = Http2Codec::new();
+        let mut buf = BytesMut::from(&b"\x00\x00\x08\x00\x09\x00\x00\x00\x01\x02hello\x00\x00"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Http2Frame::Data {
+                stream_id: 1,
+                end_stream: true,
+                data: Bytes::from_static(b"hello"),
+            })
+        );
+    }
+
+    #[test]
+    fn enforces_max_frame_size() {
+        let mut codec = Http2Codec::new();
+        let mut buf = BytesMut::new();
+        let frame = Http2Frame::Data {
+            stream_id: 1,
+            end_stream: false,
+            data: Bytes::from(vec![0; 20000]),
+        };
+        assert!(matches!(
+            codec.encode(frame.clone(), &mut buf),
+            Err(Http2Error::FrameTooLarge(20000))
+        ));
+
+        // The peer's limit lets us send larger frames, but we still only accept our own.
+        codec.set_max_send_frame_size(32768).unwrap();
+        codec.encode(frame.clone(), &mut buf).unwrap();
+        assert!(matches!(codec.decode(&mut buf), Err(Http2Error::FrameTooLarge(20000))));
+
+        codec.set_max_recv_frame_size(32768).unwrap();
+        let mut buf = BytesMut::new();
+        codec.encode(frame.clone(), &mut buf).unwrap();
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(frame));
+
+        let mut buf = BytesMut::from(&b"\x00\x40\x01\x00\x00\x00\x00\x00\x01"[..]);
+        codec.set_max_recv_frame_size(16384).unwrap();
+        assert!(matches!(codec.decode(&mut buf), Err(Http2Error::FrameTooLarge(16385))));
+        assert!(codec.set_max_recv_frame_size(1024).is_err());
+        assert!(codec.set_max_send_frame_size(1 << 24).is_err());
+    }
+
+    #[test]
+    fn huge_lengths_do_not_allocate() {
+        let mut codec = Http2Codec::new();
+        codec.set_max_recv_frame_size(MAX_MAX_FRAME_SIZE).unwrap();
+        let mut buf = BytesMut::from(&b"\xff\xff\xff\x00\x00\x00\x00\x00\x01"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.capacity() < 1 << 20);
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        let mut codec = Http2Codec::server();
+        let mut buf = BytesMut::from(&b"GET / HTTP/1.1\r\n"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(Http2Error::InvalidPreface)));
+
+        let mut codec = Http2Codec::new();
+        for input in [
+            // DATA on stream 0
+            &b"\x00\x00\x00\x00\x00\x00\x00\x00\x00"[..],
+            // SETTINGS on stream 1
+            b"\x00\x00\x00\x04\x00\x00\x00\x00\x01",
+            // PING with 4 bytes
+            b"\x00\x00\x04\x06\x00\x00\x00\x00\x00ping",
+        ] {
+            let mut buf = BytesMut::from(input);
+            assert!(matches!(codec.decode(&mut buf), Err(Http2Error::InvalidFrame(_))));
+        }
+    }
+
+    #[test]
+    fn invalid_frames_are_skipped() {
+        let mut codec = Http2Codec::new();
+        let mut buf = BytesMut::from(&b"\x00\x00\x04\x06\x00\x00\x00\x00\x00ping"[..]);
+        codec
+            .encode(
+                Http2Frame::WindowUpdate {
+                    stream_id: 0,
+                    increment: 1,
+                },
+                &mut buf,
+            )
+            .unwrap();
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(codec.is_recoverable(&err));
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Http2Frame::WindowUpdate {
+                stream_id: 0,
+                increment: 1,
+            })
+        );
+
+        let mut buf = BytesMut::from(&b"\x00\x40\x01\x00\x00\x00\x00\x00\x01"[..]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(!codec.is_recoverable(&err));
+    }
+}
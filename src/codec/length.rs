@@ -1,4 +1,4 @@
-use super::{Decoder, Encoder};
+use super::{Decoder, EncodeBuf, Encoder};
 use bytes::{Buf, Bytes, BytesMut};
 use std::{convert::TryFrom, marker::PhantomData};
 
@@ -50,6 +50,12 @@ impl<L: Length> Encoder for LengthCodec<L> {
         dst.extend_from_slice(&src);
         Ok(())
     }
+
+    fn encode_vectored(&mut self, src: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        L::encode(src.len(), dst.buffer_mut())?;
+        dst.push_bytes(src);
+        Ok(())
+    }
 }
 
 impl<L: Length> Decoder for LengthCodec<L> {
@@ -1,4 +1,4 @@
-use super::{Decoder, Encoder};
+use super::{Decoder, EncodeBuf, Encoder};
 use bytes::{Buf, BytesMut};
 
 #[allow(missing_docs)]
@@ -70,6 +70,19 @@ where
         dst.unsplit(tmp_dst);
         Ok(())
     }
+
+    fn encode_vectored(&mut self, src: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        let start = dst.len();
+        let result = match self.inner.encode_vectored(src, dst) {
+            Ok(()) if dst.len() - start > self.max_frame_size => Err(LimitError::LimitExceeded(dst.len() - start)),
+            Ok(()) => Ok(()),
+            Err(err) => Err(LimitError::Inner(err)),
+        };
+        if result.is_err() {
+            dst.truncate(start);
+        }
+        result
+    }
 }
 
 impl<C> Decoder for LimitCodec<C>
@@ -110,4 +123,13 @@ where
             Err(x) => Err(LimitError::Inner(x)),
         }
     }
+
+    // An oversized frame is skipped on the following calls, but a defunct decoder stays so.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        match err {
+            LimitError::LimitExceeded(_) => true,
+            LimitError::Defunct => false,
+            LimitError::Inner(err) => self.inner.is_recoverable(err),
+        }
+    }
 }
@@ -25,6 +25,9 @@ macro_rules! impl_phantom {
     }
 }
 
+mod encode_buf;
+pub use self::encode_buf::EncodeBuf;
+
 mod bytes;
 pub use self::bytes::BytesCodec;
 
@@ -34,6 +37,101 @@ pub use self::length::{LengthCodec, OverflowError};
 mod limit;
 pub use self::limit::{DecoderWithSkipAhead, LimitCodec, LimitError, SkipAheadHandler};
 
+mod chunked;
+pub use self::chunked::{Chunk, ChunkedCodec, ChunkedError};
+
+mod http;
+pub use self::http::{
+    HttpError,
+    HttpHead,
+    HttpHeadCodec,
+    HttpVersion,
+    RequestHead,
+    RequestHeadCodec,
+    ResponseHead,
+    ResponseHeadCodec,
+};
+
+mod sse;
+pub use self::sse::{SseCodec, SseError, SseEvent};
+
+#[cfg(feature = "websocket")]
+mod websocket;
+#[cfg(feature = "websocket")]
+pub use self::websocket::{Opcode, Role, WebSocketCodec, WebSocketError, WebSocketFrame};
+
+mod http2;
+pub use self::http2::{Http2Codec, Http2Error, Http2Frame, Http2Priority};
+
+mod grpc;
+pub use self::grpc::{GrpcCodec, GrpcError, GrpcPayloadCodec};
+
+mod mqtt;
+pub use self::mqtt::{
+    ConnAck,
+    Connect,
+    MqttCodec,
+    MqttError,
+    MqttPacket,
+    MqttVersion,
+    Properties,
+    PropertyValue,
+    PubResponse,
+    Publish,
+    QoS,
+    ReasonPacket,
+    SubAck,
+    Subscribe,
+    SubscriptionOptions,
+    Unsubscribe,
+    Will,
+};
+
+mod postgres;
+pub use self::postgres::{PgCodec, PgError, PgMessage, PgPhase};
+
+mod netstring;
+pub use self::netstring::{NetstringCodec, NetstringError, NetstringSkipAhead};
+
+mod pkt_line;
+pub use self::pkt_line::{PktLine, PktLineCodec, PktLineError};
+
+mod resp;
+pub use self::resp::{RespCodec, RespError, RespValue, RespVersion};
+
+mod payload;
+pub use self::payload::{PayloadCodec, PayloadError};
+
+mod pair;
+pub use self::pair::PairCodec;
+
+mod checksum;
+#[cfg(feature = "adler32")]
+pub use self::checksum::Adler32;
+#[cfg(feature = "crc32c")]
+pub use self::checksum::Crc32c;
+#[cfg(feature = "xxhash")]
+pub use self::checksum::XxHash64;
+pub use self::checksum::{Checksum, ChecksumCodec, ChecksumError};
+
+mod compression;
+#[cfg(feature = "deflate")]
+pub use self::compression::Deflate;
+#[cfg(feature = "lz4")]
+pub use self::compression::Lz4;
+#[cfg(feature = "zstd")]
+pub use self::compression::Zstd;
+pub use self::compression::{Compression, CompressionCodec, CompressionError};
+
+#[cfg(feature = "aead")]
+mod aead;
+#[cfg(feature = "aesgcm")]
+pub use self::aead::Aes256GcmCodec;
+#[cfg(feature = "chacha20")]
+pub use self::aead::ChaCha20Poly1305Codec;
+#[cfg(feature = "aead")]
+pub use self::aead::{AeadCodec, AeadError, Side};
+
 #[cfg(feature = "lines")]
 mod lines;
 #[cfg(feature = "lines")]
@@ -49,6 +147,11 @@ mod json;
 #[cfg(feature = "json")]
 pub use self::json::JsonCodec;
 
+#[cfg(feature = "hdlc")]
+mod hdlc;
+#[cfg(feature = "hdlc")]
+pub use self::hdlc::{Fcs, HdlcCodec, HdlcError, HdlcSkipAhead};
+
 pub trait Decoder {
     type Item;
     type Error: std::error::Error + 'static;
@@ -58,6 +161,18 @@ pub trait Decoder {
     fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         self.decode(src)
     }
+
+    // Whether decoding can carry on after `err`, for framed types configured to resume after
+    // errors. Only errors after which the offending bytes have been consumed qualify, or the
+    // same error would be returned again.
+    //
+    // Among the protocol codecs, `MqttCodec`, `Http2Codec` and `PktLineCodec` recover from
+    // errors in a frame they have consumed whole, and `PayloadCodec` and `GrpcPayloadCodec` from
+    // errors in a payload. `WebSocketCodec`, `RespCodec`, `GrpcCodec` and `PgCodec` discard their
+    // buffer on every error, so none of their errors are recoverable.
+    fn is_recoverable(&self, _err: &Self::Error) -> bool {
+        false
+    }
 }
 
 pub trait Encoder {
@@ -65,4 +180,10 @@ pub trait Encoder {
     type Error: std::error::Error + 'static;
 
     fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error>;
+
+    // Used by the framed types. Codecs that carry large `Bytes` payloads can override this to
+    // queue them with `EncodeBuf::push_bytes` rather than copying them into the write buffer.
+    fn encode_vectored(&mut self, item: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        self.encode(item, dst.buffer_mut())
+    }
 }
@@ -0,0 +1,1077 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+// The largest value the four-byte remaining length can carry.
+const MAX_REMAINING_LEN: usize = 268_435_455;
+const DEFAULT_MAX_PACKET_SIZE: usize = 1 << 20;
+// The most buffer space reserved ahead of a packet that has not arrived yet, so that a large
+// announced length costs nothing until the peer actually sends it.
+const MAX_RESERVE: usize = 64 * 1024;
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const PUBREC: u8 = 5;
+const PUBREL: u8 = 6;
+const PUBCOMP: u8 = 7;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const UNSUBSCRIBE: u8 = 10;
+const UNSUBACK: u8 = 11;
+const PINGREQ: u8 = 12;
+const PINGRESP: u8 = 13;
+const DISCONNECT: u8 = 14;
+const AUTH: u8 = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MqttVersion {
+    V311,
+    V5,
+}
+
+impl MqttVersion {
+    const fn level(self) -> u8 {
+        match self {
+            MqttVersion::V311 => 4,
+            MqttVersion::V5 => 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QoS {
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl QoS {
+    fn from_bits(bits: u8) -> Result<Self, MqttError> {
+        match bits {
+            0 => Ok(QoS::AtMostOnce),
+            1 => Ok(QoS::AtLeastOnce),
+            2 => Ok(QoS::ExactlyOnce),
+            _ => Err(MqttError::Malformed("invalid QoS")),
+        }
+    }
+
+    const fn bits(self) -> u8 {
+        match self {
+            QoS::AtMostOnce => 0,
+            QoS::AtLeastOnce => 1,
+            QoS::ExactlyOnce => 2,
+        }
+    }
+}
+
+// The value of an MQTT 5 property, whose type is fixed by the property identifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PropertyValue {
+    Byte(u8),
+    TwoByte(u16),
+    FourByte(u32),
+    VarInt(u32),
+    String(String),
+    Binary(Bytes),
+    StringPair(String, String),
+}
+
+#[derive(Clone, Copy)]
+enum PropertyKind {
+    Byte,
+    TwoByte,
+    FourByte,
+    VarInt,
+    String,
+    Binary,
+    StringPair,
+}
+
+fn property_kind(id: u8) -> Result<PropertyKind, MqttError> {
+    Ok(match id {
+        0x01 | 0x17 | 0x19 | 0x24 | 0x25 | 0x28 | 0x29 | 0x2a => PropertyKind::Byte,
+        0x13 | 0x21 | 0x22 | 0x23 => PropertyKind::TwoByte,
+        0x02 | 0x11 | 0x18 | 0x27 => PropertyKind::FourByte,
+        0x0b => PropertyKind::VarInt,
+        0x03 | 0x08 | 0x12 | 0x15 | 0x1a | 0x1c | 0x1f => PropertyKind::String,
+        0x09 | 0x16 => PropertyKind::Binary,
+        0x26 => PropertyKind::StringPair,
+        id => return Err(MqttError::InvalidProperty(id)),
+    })
+}
+
+// Property lists are empty for MQTT 3.1.1 packets, and are never written for them.
+pub type Properties = Vec<(u8, PropertyValue)>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Will {
+    pub topic: String,
+    pub payload: Bytes,
+    pub qos: QoS,
+    pub retain: bool,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Connect {
+    pub version: MqttVersion,
+    pub clean_start: bool,
+    pub keep_alive: u16,
+    pub properties: Properties,
+    pub client_id: String,
+    pub will: Option<Will>,
+    pub username: Option<String>,
+    pub password: Option<Bytes>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnAck {
+    pub session_present: bool,
+    pub code: u8,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Publish {
+    pub dup: bool,
+    pub qos: QoS,
+    pub retain: bool,
+    pub topic: String,
+    // Present exactly when `qos` is above `AtMostOnce`.
+    pub packet_id: Option<u16>,
+    pub properties: Properties,
+    pub payload: Bytes,
+}
+
+// The body shared by PUBACK, PUBREC, PUBREL and PUBCOMP.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PubResponse {
+    pub packet_id: u16,
+    pub reason: u8,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubscriptionOptions {
+    pub qos: QoS,
+    pub no_local: bool,
+    pub retain_as_published: bool,
+    pub retain_handling: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subscribe {
+    pub packet_id: u16,
+    pub properties: Properties,
+    pub filters: Vec<(String, SubscriptionOptions)>,
+}
+
+// Used for both SUBACK and UNSUBACK; UNSUBACK carries no reason codes in MQTT 3.1.1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubAck {
+    pub packet_id: u16,
+    pub properties: Properties,
+    pub reasons: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unsubscribe {
+    pub packet_id: u16,
+    pub properties: Properties,
+    pub filters: Vec<String>,
+}
+
+// Used for both DISCONNECT and AUTH. An MQTT 3.1.1 DISCONNECT has neither field.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReasonPacket {
+    pub reason: u8,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MqttPacket {
+    Connect(Connect),
+    ConnAck(ConnAck),
+    Publish(Publish),
+    PubAck(PubResponse),
+    PubRec(PubResponse),
+    PubRel(PubResponse),
+    PubComp(PubResponse),
+    Subscribe(Subscribe),
+    SubAck(SubAck),
+    Unsubscribe(Unsubscribe),
+    UnsubAck(SubAck),
+    PingReq,
+    PingResp,
+    Disconnect(ReasonPacket),
+    Auth(ReasonPacket),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MqttCodec {
+    version: MqttVersion,
+    max_packet_size: usize,
+}
+
+impl MqttCodec {
+    // Packets are limited to 1 MiB; use `with_max_packet_size` to accept up to the protocol's
+    // own limit of 256 MiB.
+    #[allow(missing_docs)]
+    pub const fn new(version: MqttVersion) -> Self {
+        Self::with_max_packet_size(version, DEFAULT_MAX_PACKET_SIZE)
+    }
+
+    #[allow(missing_docs)]
+    pub const fn with_max_packet_size(version: MqttVersion, max_packet_size: usize) -> Self {
+        Self {
+            version,
+            max_packet_size,
+        }
+    }
+
+    // The protocol version in use. Encoding or decoding a CONNECT packet switches to the version
+    // it names, so a server can start with either.
+    #[allow(missing_docs)]
+    pub fn version(&self) -> MqttVersion {
+        self.version
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+
+    fn v5(&self) -> bool {
+        self.version == MqttVersion::V5
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MqttError {
+    #[error("packet of {0} bytes exceeds maximum packet size")]
+    PacketTooLarge(usize),
+    #[error("invalid packet type {0}")]
+    InvalidPacketType(u8),
+    #[error("invalid fixed header flags {0:#06b}")]
+    InvalidFlags(u8),
+    #[error("unsupported protocol level {0}")]
+    UnsupportedVersion(u8),
+    #[error("invalid property {0:#04x}")]
+    InvalidProperty(u8),
+    #[error("malformed packet: {0}")]
+    Malformed(&'static str),
+    #[error("invalid remaining length: {0}")]
+    InvalidRemainingLength(&'static str),
+}
+
+fn decode_varint(src: &[u8]) -> Result<Option<(usize, usize)>, &'static str> {
+    let mut value = 0;
+    for i in 0 .. 4 {
+        let Some(&byte) = src.get(i) else {
+            return Ok(None);
+        };
+        value |= usize::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            // A trailing zero byte only pads a value that fits in fewer bytes.
+            if i > 0 && byte == 0 {
+                return Err("variable byte integer not minimally encoded");
+            }
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    Err("variable byte integer exceeds 4 bytes")
+}
+
+fn put_varint(dst: &mut BytesMut, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            dst.put_u8(byte);
+            return;
+        }
+        dst.put_u8(byte | 0x80);
+    }
+}
+
+const TRUNCATED: MqttError = MqttError::Malformed("truncated packet");
+
+fn take_u8(buf: &mut Bytes) -> Result<u8, MqttError> {
+    if buf.is_empty() {
+        return Err(TRUNCATED);
+    }
+    Ok(buf.get_u8())
+}
+
+fn take_u16(buf: &mut Bytes) -> Result<u16, MqttError> {
+    if buf.len() < 2 {
+        return Err(TRUNCATED);
+    }
+    Ok(buf.get_u16())
+}
+
+fn take_u32(buf: &mut Bytes) -> Result<u32, MqttError> {
+    if buf.len() < 4 {
+        return Err(TRUNCATED);
+    }
+    Ok(buf.get_u32())
+}
+
+fn take_varint(buf: &mut Bytes) -> Result<usize, MqttError> {
+    let (value, len) = decode_varint(buf).map_err(MqttError::Malformed)?.ok_or(TRUNCATED)?;
+    buf.advance(len);
+    Ok(value)
+}
+
+fn take_binary(buf: &mut Bytes) -> Result<Bytes, MqttError> {
+    let len = usize::from(take_u16(buf)?);
+    if buf.len() < len {
+        return Err(TRUNCATED);
+    }
+    Ok(buf.split_to(len))
+}
+
+fn take_string(buf: &mut Bytes) -> Result<String, MqttError> {
+    let data = take_binary(buf)?;
+    match std::str::from_utf8(&data) {
+        Ok(string) if !string.contains('\0') => Ok(string.to_owned()),
+        _ => Err(MqttError::Malformed("invalid UTF-8 string")),
+    }
+}
+
+fn put_binary(dst: &mut BytesMut, data: &[u8]) -> Result<(), MqttError> {
+    let len = u16::try_from(data.len()).map_err(|_| MqttError::Malformed("string or binary field too long"))?;
+    dst.put_u16(len);
+    dst.put_slice(data);
+    Ok(())
+}
+
+fn take_properties(buf: &mut Bytes) -> Result<Properties, MqttError> {
+    let len = take_varint(buf)?;
+    if buf.len() < len {
+        return Err(TRUNCATED);
+    }
+    let mut buf = buf.split_to(len);
+    let mut properties = Vec::new();
+    while !buf.is_empty() {
+        let id = take_u8(&mut buf)?;
+        let value = match property_kind(id)? {
+            PropertyKind::Byte => PropertyValue::Byte(take_u8(&mut buf)?),
+            PropertyKind::TwoByte => PropertyValue::TwoByte(take_u16(&mut buf)?),
+            PropertyKind::FourByte => PropertyValue::FourByte(take_u32(&mut buf)?),
+            PropertyKind::VarInt => PropertyValue::VarInt(take_varint(&mut buf)? as u32),
+            PropertyKind::String => PropertyValue::String(take_string(&mut buf)?),
+            PropertyKind::Binary => PropertyValue::Binary(take_binary(&mut buf)?),
+            PropertyKind::StringPair => PropertyValue::StringPair(take_string(&mut buf)?, take_string(&mut buf)?),
+        };
+        properties.push((id, value));
+    }
+    Ok(properties)
+}
+
+fn put_properties(dst: &mut BytesMut, properties: &Properties) -> Result<(), MqttError> {
+    let mut buf = BytesMut::new();
+    for (id, value) in properties {
+        buf.put_u8(*id);
+        match (property_kind(*id)?, value) {
+            (PropertyKind::Byte, PropertyValue::Byte(value)) => buf.put_u8(*value),
+            (PropertyKind::TwoByte, PropertyValue::TwoByte(value)) => buf.put_u16(*value),
+            (PropertyKind::FourByte, PropertyValue::FourByte(value)) => buf.put_u32(*value),
+            (PropertyKind::VarInt, PropertyValue::VarInt(value)) if *value as usize <= MAX_REMAINING_LEN => {
+                put_varint(&mut buf, *value as usize)
+            },
+            (PropertyKind::String, PropertyValue::String(value)) => put_binary(&mut buf, value.as_bytes())?,
+            (PropertyKind::Binary, PropertyValue::Binary(value)) => put_binary(&mut buf, value)?,
+            (PropertyKind::StringPair, PropertyValue::StringPair(key, value)) => {
+                put_binary(&mut buf, key.as_bytes())?;
+                put_binary(&mut buf, value.as_bytes())?;
+            },
+            _ => return Err(MqttError::InvalidProperty(*id)),
+        }
+    }
+    put_varint(dst, buf.len());
+    dst.put_slice(&buf);
+    Ok(())
+}
+
+fn expect_end(buf: &Bytes) -> Result<(), MqttError> {
+    match buf.is_empty() {
+        true => Ok(()),
+        false => Err(MqttError::Malformed("trailing bytes")),
+    }
+}
+
+impl MqttCodec {
+    fn take_properties(&self, buf: &mut Bytes) -> Result<Properties, MqttError> {
+        match self.v5() {
+            true => take_properties(buf),
+            false => Ok(Vec::new()),
+        }
+    }
+
+    fn put_properties(&self, dst: &mut BytesMut, properties: &Properties) -> Result<(), MqttError> {
+        match self.v5() {
+            true => put_properties(dst, properties),
+            false => Ok(()),
+        }
+    }
+
+    fn decode_connect(buf: &mut Bytes) -> Result<Connect, MqttError> {
+        if take_string(buf)? != "MQTT" {
+            return Err(MqttError::Malformed("invalid protocol name"));
+        }
+        // The codec only switches to the peer's version once the whole packet has parsed.
+        let version = match take_u8(buf)? {
+            4 => MqttVersion::V311,
+            5 => MqttVersion::V5,
+            level => return Err(MqttError::UnsupportedVersion(level)),
+        };
+        let take_properties = |buf: &mut Bytes| match version {
+            MqttVersion::V5 => take_properties(buf),
+            MqttVersion::V311 => Ok(Vec::new()),
+        };
+        let flags = take_u8(buf)?;
+        let will_qos = QoS::from_bits((flags >> 3) & 0x3)?;
+        if flags & 0x01 != 0 || (flags & 0x04 == 0 && flags & 0x38 != 0) {
+            return Err(MqttError::Malformed("invalid connect flags"));
+        }
+        if version == MqttVersion::V311 && flags & 0xc0 == 0x40 {
+            return Err(MqttError::Malformed("password without username"));
+        }
+        let keep_alive = take_u16(buf)?;
+        let properties = take_properties(buf)?;
+        let client_id = take_string(buf)?;
+        let will = if flags & 0x04 != 0 {
+            let properties = take_properties(buf)?;
+            Some(Will {
+                topic: take_string(buf)?,
+                payload: take_binary(buf)?,
+                qos: will_qos,
+                retain: flags & 0x20 != 0,
+                properties,
+            })
+        } else {
+            None
+        };
+        let username = if flags & 0x80 != 0 {
+            Some(take_string(buf)?)
+        } else {
+            None
+        };
+        let password = if flags & 0x40 != 0 {
+            Some(take_binary(buf)?)
+        } else {
+            None
+        };
+        expect_end(buf)?;
+        Ok(Connect {
+            version,
+            clean_start: flags & 0x02 != 0,
+            keep_alive,
+            properties,
+            client_id,
+            will,
+            username,
+            password,
+        })
+    }
+
+    fn decode_pub_response(&self, buf: &mut Bytes) -> Result<PubResponse, MqttError> {
+        let packet_id = take_u16(buf)?;
+        let mut response = PubResponse {
+            packet_id,
+            reason: 0,
+            properties: Vec::new(),
+        };
+        if self.v5() && !buf.is_empty() {
+            response.reason = take_u8(buf)?;
+            if !buf.is_empty() {
+                response.properties = take_properties(buf)?;
+            }
+        }
+        expect_end(buf)?;
+        Ok(response)
+    }
+
+    fn decode_reason(&self, buf: &mut Bytes) -> Result<ReasonPacket, MqttError> {
+        let mut packet = ReasonPacket::default();
+        if self.v5() && !buf.is_empty() {
+            packet.reason = take_u8(buf)?;
+            if !buf.is_empty() {
+                packet.properties = take_properties(buf)?;
+            }
+        }
+        expect_end(buf)?;
+        Ok(packet)
+    }
+
+    fn decode_subscribe(&self, buf: &mut Bytes) -> Result<Subscribe, MqttError> {
+        let packet_id = take_u16(buf)?;
+        let properties = self.take_properties(buf)?;
+        let mut filters = Vec::new();
+        while !buf.is_empty() {
+            let filter = take_string(buf)?;
+            let options = take_u8(buf)?;
+            let reserved = if self.v5() { 0xc0 } else { 0xfc };
+            if options & reserved != 0 || (options >> 4) & 0x3 == 3 {
+                return Err(MqttError::Malformed("invalid subscription options"));
+            }
+            filters.push((filter, SubscriptionOptions {
+                qos: QoS::from_bits(options & 0x3)?,
+                no_local: options & 0x04 != 0,
+                retain_as_published: options & 0x08 != 0,
+                retain_handling: (options >> 4) & 0x3,
+            }));
+        }
+        if filters.is_empty() {
+            return Err(MqttError::Malformed("SUBSCRIBE without topic filters"));
+        }
+        Ok(Subscribe {
+            packet_id,
+            properties,
+            filters,
+        })
+    }
+
+    fn decode_unsubscribe(&self, buf: &mut Bytes) -> Result<Unsubscribe, MqttError> {
+        let packet_id = take_u16(buf)?;
+        let properties = self.take_properties(buf)?;
+        let mut filters = Vec::new();
+        while !buf.is_empty() {
+            filters.push(take_string(buf)?);
+        }
+        if filters.is_empty() {
+            return Err(MqttError::Malformed("UNSUBSCRIBE without topic filters"));
+        }
+        Ok(Unsubscribe {
+            packet_id,
+            properties,
+            filters,
+        })
+    }
+
+    fn decode_suback(&self, kind: u8, buf: &mut Bytes) -> Result<SubAck, MqttError> {
+        let packet_id = take_u16(buf)?;
+        let properties = self.take_properties(buf)?;
+        if kind == UNSUBACK && !self.v5() {
+            expect_end(buf)?;
+        }
+        Ok(SubAck {
+            packet_id,
+            properties,
+            reasons: std::mem::take(buf).to_vec(),
+        })
+    }
+
+    fn decode_packet(&mut self, kind: u8, flags: u8, mut buf: Bytes) -> Result<MqttPacket, MqttError> {
+        let buf = &mut buf;
+        let expected_flags = match kind {
+            PUBLISH => flags,
+            PUBREL | SUBSCRIBE | UNSUBSCRIBE => 0b0010,
+            _ => 0,
+        };
+        if flags != expected_flags {
+            return Err(MqttError::InvalidFlags(flags));
+        }
+
+        Ok(match kind {
+            CONNECT => {
+                let connect = Self::decode_connect(buf)?;
+                self.version = connect.version;
+                MqttPacket::Connect(connect)
+            },
+            CONNACK => {
+                let ack_flags = take_u8(buf)?;
+                if ack_flags & 0xfe != 0 {
+                    return Err(MqttError::Malformed("invalid CONNACK flags"));
+                }
+                let code = take_u8(buf)?;
+                let properties = self.take_properties(buf)?;
+                expect_end(buf)?;
+                MqttPacket::ConnAck(ConnAck {
+                    session_present: ack_flags & 0x01 != 0,
+                    code,
+                    properties,
+                })
+            },
+            PUBLISH => {
+                let qos = QoS::from_bits((flags >> 1) & 0x3)?;
+                let topic = take_string(buf)?;
+                let packet_id = if qos == QoS::AtMostOnce {
+                    None
+                } else {
+                    Some(take_u16(buf)?)
+                };
+                let properties = self.take_properties(buf)?;
+                MqttPacket::Publish(Publish {
+                    dup: flags & 0x08 != 0,
+                    qos,
+                    retain: flags & 0x01 != 0,
+                    topic,
+                    packet_id,
+                    properties,
+                    payload: std::mem::take(buf),
+                })
+            },
+            PUBACK => MqttPacket::PubAck(self.decode_pub_response(buf)?),
+            PUBREC => MqttPacket::PubRec(self.decode_pub_response(buf)?),
+            PUBREL => MqttPacket::PubRel(self.decode_pub_response(buf)?),
+            PUBCOMP => MqttPacket::PubComp(self.decode_pub_response(buf)?),
+            SUBSCRIBE => MqttPacket::Subscribe(self.decode_subscribe(buf)?),
+            SUBACK => MqttPacket::SubAck(self.decode_suback(kind, buf)?),
+            UNSUBSCRIBE => MqttPacket::Unsubscribe(self.decode_unsubscribe(buf)?),
+            UNSUBACK => MqttPacket::UnsubAck(self.decode_suback(kind, buf)?),
+            PINGREQ | PINGRESP => {
+                expect_end(buf)?;
+                if kind == PINGREQ {
+                    MqttPacket::PingReq
+                } else {
+                    MqttPacket::PingResp
+                }
+            },
+            DISCONNECT => MqttPacket::Disconnect(self.decode_reason(buf)?),
+            AUTH if self.v5() => MqttPacket::Auth(self.decode_reason(buf)?),
+            kind => return Err(MqttError::InvalidPacketType(kind)),
+        })
+    }
+
+    fn encode_pub_response(&self, response: &PubResponse, dst: &mut BytesMut) -> Result<(), MqttError> {
+        dst.put_u16(response.packet_id);
+        if self.v5() && (response.reason != 0 || !response.properties.is_empty()) {
+            dst.put_u8(response.reason);
+            put_properties(dst, &response.properties)?;
+        }
+        Ok(())
+    }
+
+    fn encode_reason(&self, packet: &ReasonPacket, dst: &mut BytesMut) -> Result<(), MqttError> {
+        if self.v5() && (packet.reason != 0 || !packet.properties.is_empty()) {
+            dst.put_u8(packet.reason);
+            put_properties(dst, &packet.properties)?;
+        }
+        Ok(())
+    }
+
+    fn encode_body(&mut self, item: &MqttPacket, body: &mut BytesMut) -> Result<(u8, u8), MqttError> {
+        Ok(match item {
+            MqttPacket::Connect(connect) => {
+                self.version = connect.version;
+                put_binary(body, b"MQTT")?;
+                body.put_u8(connect.version.level());
+                let mut flags = u8::from(connect.clean_start) << 1;
+                if let Some(will) = &connect.will {
+                    flags |= 0x04 | will.qos.bits() << 3 | u8::from(will.retain) << 5;
+                }
+                flags |= u8::from(connect.password.is_some()) << 6 | u8::from(connect.username.is_some()) << 7;
+                body.put_u8(flags);
+                body.put_u16(connect.keep_alive);
+                self.put_properties(body, &connect.properties)?;
+                put_binary(body, connect.client_id.as_bytes())?;
+                if let Some(will) = &connect.will {
+                    self.put_properties(body, &will.properties)?;
+                    put_binary(body, will.topic.as_bytes())?;
+                    put_binary(body, &will.payload)?;
+                }
+                if let Some(username) = &connect.username {
+                    put_binary(body, username.as_bytes())?;
+                }
+                if let Some(password) = &connect.password {
+                    put_binary(body, password)?;
+                }
+                (CONNECT, 0)
+            },
+            MqttPacket::ConnAck(ack) => {
+                body.put_u8(u8::from(ack.session_present));
+                body.put_u8(ack.code);
+                self.put_properties(body, &ack.properties)?;
+                (CONNACK, 0)
+            },
+            MqttPacket::Publish(publish) => {
+                put_binary(body, publish.topic.as_bytes())?;
+                match (publish.qos, publish.packet_id) {
+                    (QoS::AtMostOnce, None) => {},
+                    (QoS::AtLeastOnce | QoS::ExactlyOnce, Some(packet_id)) => body.put_u16(packet_id),
+                    _ => return Err(MqttError::Malformed("packet identifier does not match QoS")),
+                }
+                self.put_properties(body, &publish.properties)?;
+                body.put_slice(&publish.payload);
+                let flags = u8::from(publish.dup) << 3 | publish.qos.bits() << 1 | u8::from(publish.retain);
+                (PUBLISH, flags)
+            },
+            MqttPacket::PubAck(response) => {
+                self.encode_pub_response(response, body)?;
+                (PUBACK, 0)
+            },
+            MqttPacket::PubRec(response) => {
+                self.encode_pub_response(response, body)?;
+                (PUBREC, 0)
+            },
+            MqttPacket::PubRel(response) => {
+                self.encode_pub_response(response, body)?;
+                (PUBREL, 0b0010)
+            },
+            MqttPacket::PubComp(response) => {
+                self.encode_pub_response(response, body)?;
+                (PUBCOMP, 0)
+            },
+            MqttPacket::Subscribe(subscribe) => {
+                body.put_u16(subscribe.packet_id);
+                self.put_properties(body, &subscribe.properties)?;
+                for (filter, options) in &subscribe.filters {
+                    put_binary(body, filter.as_bytes())?;
+                    // MQTT 3.1.1 reserves every bit above the QoS.
+                    body.put_u8(match self.v5() {
+                        true => {
+                            options.qos.bits()
+                                | u8::from(options.no_local) << 2
+                                | u8::from(options.retain_as_published) << 3
+                                | (options.retain_handling & 0x3) << 4
+                        },
+                        false => options.qos.bits(),
+                    });
+                }
+                (SUBSCRIBE, 0b0010)
+            },
+            MqttPacket::SubAck(ack) | MqttPacket::UnsubAck(ack) => {
+                body.put_u16(ack.packet_id);
+                self.put_properties(body, &ack.properties)?;
+                let kind = if matches!(item, MqttPacket::SubAck(_)) {
+                    SUBACK
+                } else {
+                    UNSUBACK
+                };
+                if kind == SUBACK || self.v5() {
+                    body.put_slice(&ack.reasons);
+                }
+                (kind, 0)
+            },
+            MqttPacket::Unsubscribe(unsubscribe) => {
+                body.put_u16(unsubscribe.packet_id);
+                self.put_properties(body, &unsubscribe.properties)?;
+                for filter in &unsubscribe.filters {
+                    put_binary(body, filter.as_bytes())?;
+                }
+                (UNSUBSCRIBE, 0b0010)
+            },
+            MqttPacket::PingReq => (PINGREQ, 0),
+            MqttPacket::PingResp => (PINGRESP, 0),
+            MqttPacket::Disconnect(packet) => {
+                self.encode_reason(packet, body)?;
+                (DISCONNECT, 0)
+            },
+            MqttPacket::Auth(_) if !self.v5() => return Err(MqttError::InvalidPacketType(AUTH)),
+            MqttPacket::Auth(packet) => {
+                self.encode_reason(packet, body)?;
+                (AUTH, 0)
+            },
+        })
+    }
+}
+
+impl Encoder for MqttCodec {
+    type Error = MqttError;
+    type Item = MqttPacket;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut body = BytesMut::new();
+        let (kind, flags) = self.encode_body(&item, &mut body)?;
+        let mut header = BytesMut::with_capacity(5);
+        header.put_u8(kind << 4 | flags);
+        put_varint(&mut header, body.len());
+        let len = header.len() + body.len();
+        if body.len() > MAX_REMAINING_LEN || len > self.max_packet_size {
+            return Err(MqttError::PacketTooLarge(len));
+        }
+        dst.reserve(len);
+        dst.put_slice(&header);
+        dst.put_slice(&body);
+        Ok(())
+    }
+}
+
+impl Decoder for MqttCodec {
+    type Error = MqttError;
+    type Item = MqttPacket;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+        let (len, len_size) = match decode_varint(&src[1 ..]) {
+            Ok(Some(len)) => len,
+            Ok(None) => return Ok(None),
+            Err(reason) => {
+                src.clear();
+                return Err(MqttError::InvalidRemainingLength(reason));
+            },
+        };
+        let packet_len = 1 + len_size + len;
+        if packet_len > self.max_packet_size {
+            // The connection has to be closed, so there is nothing to resynchronize with.
+            src.clear();
+            return Err(MqttError::PacketTooLarge(packet_len));
+        }
+        if src.len() < packet_len {
+            src.reserve((packet_len - src.len()).min(MAX_RESERVE));
+            return Ok(None);
+        }
+
+        let header = src[0];
+        src.advance(1 + len_size);
+        let body = src.split_to(len).freeze();
+        self.decode_packet(header >> 4, header & 0x0f, body).map(Some)
+    }
+
+    // Errors in the body of a packet come after the whole packet has been consumed; only a bad
+    // or oversized remaining length leaves nothing to resynchronize with.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        !matches!(err, MqttError::PacketTooLarge(_) | MqttError::InvalidRemainingLength(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(codec: &mut MqttCodec, packet: MqttPacket) -> BytesMut {
+        let mut buf = BytesMut::new();
+        codec.encode(packet.clone(), &mut buf).unwrap();
+        let encoded = buf.clone();
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(packet));
+        assert!(buf.is_empty());
+        encoded
+    }
+
+    #[test]
+    fn v311_packets() {
+        let mut codec = MqttCodec::new(MqttVersion::V311);
+        let connect = roundtrip(
+            &mut codec,
+            MqttPacket::Connect(Connect {
+                version: MqttVersion::V311,
+                clean_start: true,
+                keep_alive: 60,
+                properties: vec![],
+                client_id: "gw".into(),
+                will: None,
+                username: None,
+                password: None,
+            }),
+        );
+        assert_eq!(&connect[..], b"\x10\x0e\x00\x04MQTT\x04\x02\x00\x3c\x00\x02gw");
+
+        roundtrip(
+            &mut codec,
+            MqttPacket::Publish(Publish {
+                dup: false,
+                qos: QoS::AtLeastOnce,
+                retain: true,
+                topic: "sensors/1".into(),
+                packet_id: Some(7),
+                properties: vec![],
+                payload: Bytes::from_static(b"21.5"),
+            }),
+        );
+        roundtrip(
+            &mut codec,
+            MqttPacket::Subscribe(Subscribe {
+                packet_id: 8,
+                properties: vec![],
+                filters: vec![("sensors/#".into(), SubscriptionOptions {
+                    qos: QoS::ExactlyOnce,
+                    no_local: false,
+                    retain_as_published: false,
+                    retain_handling: 0,
+                })],
+            }),
+        );
+        roundtrip(
+            &mut codec,
+            MqttPacket::UnsubAck(SubAck {
+                packet_id: 9,
+                properties: vec![],
+                reasons: vec![],
+            }),
+        );
+        assert_eq!(&roundtrip(&mut codec, MqttPacket::PingReq)[..], b"\xc0\x00");
+        roundtrip(&mut codec, MqttPacket::Disconnect(ReasonPacket::default()));
+    }
+
+    #[test]
+    fn v5_properties() {
+        let mut codec = MqttCodec::new(MqttVersion::V5);
+        roundtrip(
+            &mut codec,
+            MqttPacket::Connect(Connect {
+                version: MqttVersion::V5,
+                clean_start: false,
+                keep_alive: 30,
+                properties: vec![
+                    (0x11, PropertyValue::FourByte(3600)),
+                    (0x21, PropertyValue::TwoByte(10)),
+                ],
+                client_id: "gw".into(),
+                will: Some(Will {
+                    topic: "status".into(),
+                    payload: Bytes::from_static(b"offline"),
+                    qos: QoS::AtLeastOnce,
+                    retain: true,
+                    properties: vec![(0x18, PropertyValue::FourByte(5))],
+                }),
+                username: Some("user".into()),
+                password: Some(Bytes::from_static(b"secret")),
+            }),
+        );
+        roundtrip(
+            &mut codec,
+            MqttPacket::Publish(Publish {
+                dup: false,
+                qos: QoS::AtMostOnce,
+                retain: false,
+                topic: "a".into(),
+                packet_id: None,
+                properties: vec![
+                    (0x0b, PropertyValue::VarInt(300)),
+                    (0x26, PropertyValue::StringPair("k".into(), "v".into())),
+                ],
+                payload: Bytes::from_static(b"x"),
+            }),
+        );
+        roundtrip(
+            &mut codec,
+            MqttPacket::PubAck(PubResponse {
+                packet_id: 1,
+                reason: 0x10,
+                properties: vec![(0x1f, PropertyValue::String("no subscribers".into()))],
+            }),
+        );
+        roundtrip(&mut codec, MqttPacket::Auth(ReasonPacket::default()));
+
+        let mut buf = BytesMut::new();
+        let bad = MqttPacket::Disconnect(ReasonPacket {
+            reason: 0,
+            properties: vec![(0x11, PropertyValue::Byte(1))],
+        });
+        assert!(matches!(
+            codec.encode(bad, &mut buf),
+            Err(MqttError::InvalidProperty(0x11))
+        ));
+    }
+
+    #[test]
+    fn v311_subscriptions_carry_only_qos() {
+        let mut codec = MqttCodec::new(MqttVersion::V311);
+        let mut buf = BytesMut::new();
+        let subscribe = MqttPacket::Subscribe(Subscribe {
+            packet_id: 1,
+            properties: vec![],
+            filters: vec![("t".into(), SubscriptionOptions {
+                qos: QoS::AtLeastOnce,
+                no_local: true,
+                retain_as_published: true,
+                retain_handling: 2,
+            })],
+        });
+        codec.encode(subscribe, &mut buf).unwrap();
+        assert_eq!(&buf[..], b"\x82\x06\x00\x01\x00\x01t\x01");
+    }
+
+    #[test]
+    fn malformed_connect_keeps_version() {
+        let mut codec = MqttCodec::new(MqttVersion::V311);
+        let mut buf = BytesMut::from(&b"\x10\x0c\x00\x04MQTT\x05\x02\x00\x3c\x05\x00"[..]);
+        assert!(codec.decode(&mut buf).is_err());
+        assert_eq!(codec.version(), MqttVersion::V311);
+    }
+
+    #[test]
+    fn remaining_length_encoding() {
+        let mut codec = MqttCodec::new(MqttVersion::V311);
+        let publish = MqttPacket::Publish(Publish {
+            dup: false,
+            qos: QoS::AtMostOnce,
+            retain: false,
+            topic: "t".into(),
+            packet_id: None,
+            properties: vec![],
+            payload: Bytes::from(vec![0; 16381]),
+        });
+        let encoded = roundtrip(&mut codec, publish);
+        assert_eq!(&encoded[.. 4], b"\x30\x80\x80\x01");
+
+        let mut buf = BytesMut::from(&b"\x30\xff\xff\xff\xff\x01"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(MqttError::InvalidRemainingLength(_))
+        ));
+
+        let mut buf = BytesMut::from(&b"\xc0\x80\x00"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(MqttError::InvalidRemainingLength(_))
+        ));
+    }
+
+    #[test]
+    fn enforces_max_packet_size() {
+        let mut codec = MqttCodec::with_max_packet_size(MqttVersion::V311, 8);
+        let mut buf = BytesMut::from(&b"\x30\x07"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(MqttError::PacketTooLarge(9))));
+
+        let mut buf = BytesMut::from(&b"\x30\x06\x00\x01t"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+
+        let mut codec = MqttCodec::new(MqttVersion::V311);
+        let mut buf = BytesMut::from(&b"\x30\xff\xff\x3f"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(MqttError::PacketTooLarge(_))));
+    }
+
+    #[test]
+    fn huge_lengths_do_not_allocate() {
+        let mut codec = MqttCodec::with_max_packet_size(MqttVersion::V311, usize::MAX);
+        let mut buf = BytesMut::from(&b"\x30\xff\xff\xff\x7f"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.capacity() < 1 << 20);
+    }
+
+    #[test]
+    fn malformed_packets_are_skipped() {
+        let mut codec = MqttCodec::new(MqttVersion::V311);
+        let mut buf = BytesMut::from(&b"\xc0\x01\x00\xd0\x00"[..]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(codec.is_recoverable(&err));
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(MqttPacket::PingResp));
+
+        let mut buf = BytesMut::from(&b"\xc0\x80\x00"[..]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(!codec.is_recoverable(&err));
+    }
+
+    #[test]
+    fn v311_unsuback_has_no_reasons() {
+        let mut codec = MqttCodec::new(MqttVersion::V311);
+        let mut buf = BytesMut::from(&b"\xb0\x02\x00\x07"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(MqttPacket::UnsubAck(SubAck {
+                packet_id: 7,
+                properties: vec![],
+                reasons: vec![],
+            }))
+        );
+
+        let mut buf = BytesMut::from(&b"\xb0\x03\x00\x07\x00"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(MqttError::Malformed(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_fixed_headers() {
+        let mut codec = MqttCodec::new(MqttVersion::V311);
+        let mut buf = BytesMut::from(&b"\x80\x05\x00\x01\x00\x00\x00"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(MqttError::InvalidFlags(0b0000))));
+
+        let mut buf = BytesMut::from(&b"\xf0\x00"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(MqttError::InvalidPacketType(15))));
+    }
+}
@@ -0,0 +1,281 @@
+use super::{Decoder, DecoderWithSkipAhead, Encoder, SkipAheadHandler};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+const DEFAULT_MAX_LEN: usize = 8 << 20;
+// The most buffer space reserved ahead of data that has not arrived yet, so that a large
+// announced length costs nothing until the peer actually sends it.
+const MAX_RESERVE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetstringCodec {
+    max_len: usize,
+    max_digits: usize,
+    skip: usize,
+}
+
+impl NetstringCodec {
+    #[allow(missing_docs)]
+    pub fn new() -> Self {
+        Self::with_max_len(DEFAULT_MAX_LEN)
+    }
+
+    #[allow(missing_docs)]
+    pub fn with_max_len(max_len: usize) -> Self {
+        Self {
+            max_len,
+            max_digits: max_len.to_string().len(),
+            skip: 0,
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    // Parses the `<len>:` prefix, returning the length and the size of the prefix.
+    fn parse_prefix(&self, src: &[u8]) -> Result<Option<(usize, usize)>, NetstringError> {
+        let mut len = 0usize;
+        for (i, &byte) in src.iter().enumerate() {
+            match byte {
+                b':' if i == 0 => return Err(NetstringError::InvalidLength),
+                b':' => return Ok(Some((len, i + 1))),
+                b'0' ..= b'9' if i == 1 && src[0] == b'0' => return Err(NetstringError::InvalidLength),
+                b'0' ..= b'9' if i >= self.max_digits => return Err(NetstringError::InvalidLength),
+                b'0' ..= b'9' => {
+                    len = len
+                        .checked_mul(10)
+                        .and_then(|len| len.checked_add(usize::from(byte - b'0')))
+                        .ok_or(NetstringError::InvalidLength)?;
+                },
+                _ => return Err(NetstringError::InvalidLength),
+            }
+        }
+        Ok(None)
+    }
+
+    fn skip(&mut self, src: &mut BytesMut) {
+        let amount = self.skip.min(src.len());
+        src.advance(amount);
+        self.skip -= amount;
+    }
+}
+
+impl Default for NetstringCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NetstringError {
+    #[error("invalid netstring length prefix")]
+    InvalidLength,
+    #[error("netstring of {0} bytes exceeds maximum length")]
+    TooLong(usize),
+    #[error("netstring is missing its trailing comma")]
+    MissingComma,
+}
+
+impl Encoder for NetstringCodec {
+    type Error = NetstringError;
+    type Item = Bytes;
+
+    fn encode(&mut self, src: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if src.len() > self.max_len {
+            return Err(NetstringError::TooLong(src.len()));
+        }
+        let prefix = src.len().to_string();
+        dst.reserve(prefix.len() + src.len() + 2);
+        dst.put_slice(prefix.as_bytes());
+        dst.put_u8(b':');
+        dst.put_slice(&src);
+        dst.put_u8(b',');
+        Ok(())
+    }
+}
+
+impl Decoder for NetstringCodec {
+    type Error = NetstringError;
+    type Item = Bytes;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.skip(src);
+        if self.skip > 0 {
+            return Ok(None);
+        }
+
+        let (len, prefix_len) = match self.parse_prefix(src) {
+            Ok(Some(prefix)) => prefix,
+            Ok(None) => return Ok(None),
+            Err(err) => {
+                // Without a valid length there is no way to find the next frame boundary.
+                src.clear();
+                return Err(err);
+            },
+        };
+
+        // The frame ends one past the data, at the comma.
+        let Some(end) = prefix_len.checked_add(len).and_then(|end| end.checked_add(1)) else {
+            src.clear();
+            return Err(NetstringError::InvalidLength);
+        };
+
+        if len > self.max_len {
+            src.advance(prefix_len);
+            self.skip = end - prefix_len;
+            self.skip(src);
+            return Err(NetstringError::TooLong(len));
+        }
+
+        if src.len() < end {
+            src.reserve((end - src.len()).min(MAX_RESERVE));
+            return Ok(None);
+        }
+
+        if src[end - 1] != b',' {
+            src.advance(end);
+            return Err(NetstringError::MissingComma);
+        }
+
+        src.advance(prefix_len);
+        let data = src.split_to(len).freeze();
+        src.advance(1);
+        Ok(Some(data))
+    }
+
+    // An oversized netstring is skipped and one without its comma consumed, but a bad length
+    // prefix leaves no way to find the next frame.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        matches!(err, NetstringError::TooLong(_) | NetstringError::MissingComma)
+    }
+}
+
+#[derive(Debug)]
+pub struct NetstringSkipAhead {
+    remaining: usize,
+}
+
+impl SkipAheadHandler for NetstringSkipAhead {
+    fn continue_skipping(self, src: &[u8]) -> anyhow::Result<(usize, Option<Self>)> {
+        let amount = self.remaining.min(src.len());
+        let remaining = self.remaining - amount;
+        Ok((amount, if remaining > 0 { Some(Self { remaining }) } else { None }))
+    }
+}
+
+impl DecoderWithSkipAhead for NetstringCodec {
+    type Handler = NetstringSkipAhead;
+
+    fn prepare_skip_ahead(&mut self, src: &mut BytesMut) -> Self::Handler {
+        match self.parse_prefix(src) {
+            Ok(Some((len, prefix_len))) => NetstringSkipAhead {
+                remaining: prefix_len.saturating_add(len).saturating_add(1),
+            },
+            _ => {
+                src.clear();
+                NetstringSkipAhead { remaining: 0 }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut codec = NetstringCodec::new();
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from_static(b"hello world!"), &mut buf).unwrap();
+        codec.encode(Bytes::new(), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"12:hello world!,0:,");
+
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(Bytes::from_static(b"hello world!"))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::new()));
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn partial_input() {
+        let mut codec = NetstringCodec::new();
+        let mut buf = BytesMut::from(&b"5:hel"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"lo,");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"hello")));
+    }
+
+    #[test]
+    fn rejects_bad_prefixes_early() {
+        let mut codec = NetstringCodec::with_max_len(999);
+        for input in [&b"x"[..], b":", b"01:", b"1000"] {
+            let mut buf = BytesMut::from(input);
+            assert!(matches!(codec.decode(&mut buf), Err(NetstringError::InvalidLength)));
+        }
+    }
+
+    #[test]
+    fn rejects_missing_comma() {
+        let mut codec = NetstringCodec::new();
+        let mut buf = BytesMut::from(&b"3:abc;1:d,"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(NetstringError::MissingComma)));
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"d")));
+    }
+
+    #[test]
+    fn skips_oversized_netstrings() {
+        let mut codec = NetstringCodec::with_max_len(12);
+        let mut buf = BytesMut::from(&b"15:0123456789"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(NetstringError::TooLong(15))));
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(b"01234,2:ok,");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"ok")));
+    }
+
+    #[test]
+    fn huge_lengths_do_not_allocate_or_overflow() {
+        let mut codec = NetstringCodec::new();
+        let mut buf = BytesMut::from(&b"99999999999999:"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(NetstringError::InvalidLength)));
+
+        let mut codec = NetstringCodec::with_max_len(usize::MAX);
+        let mut buf = BytesMut::from(format!("{}:", usize::MAX).as_bytes());
+        assert!(matches!(codec.decode(&mut buf), Err(NetstringError::InvalidLength)));
+
+        let mut buf = BytesMut::from(&b"99999999999999:"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.capacity() < 1 << 20);
+    }
+
+    #[test]
+    fn limit_codec_discards_oversized_vectored_frames() {
+        use crate::codec::{EncodeBuf, LimitCodec};
+
+        let mut codec = LimitCodec::new(NetstringCodec::new(), 8);
+        let mut buf = EncodeBuf::new();
+        codec.encode_vectored(Bytes::from_static(b"ok"), &mut buf).unwrap();
+        assert!(
+            codec
+                .encode_vectored(Bytes::from_static(b"too long"), &mut buf)
+                .is_err()
+        );
+        assert_eq!(&BytesMut::from(buf)[..], b"2:ok,");
+    }
+
+    #[test]
+    fn recovers_under_limit_codec() {
+        use crate::codec::LimitCodec;
+
+        let mut codec = LimitCodec::new(NetstringCodec::new(), 8);
+        let mut buf = BytesMut::from(&b"10:0123456"[..]);
+        assert!(codec.decode(&mut buf).is_err());
+
+        buf.extend_from_slice(b"789,2:ok,");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"ok")));
+    }
+}
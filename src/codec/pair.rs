@@ -0,0 +1,88 @@
+use super::{Decoder, EncodeBuf, Encoder};
+use bytes::BytesMut;
+
+// Decodes with one codec and encodes with another, for protocols whose two directions are
+// framed differently.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PairCodec<D, E> {
+    decoder: D,
+    encoder: E,
+}
+
+impl<D, E> PairCodec<D, E> {
+    #[allow(missing_docs)]
+    pub const fn new(decoder: D, encoder: E) -> Self {
+        Self { decoder, encoder }
+    }
+
+    #[allow(missing_docs)]
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    #[allow(missing_docs)]
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+
+    #[allow(missing_docs)]
+    pub fn encoder(&self) -> &E {
+        &self.encoder
+    }
+
+    #[allow(missing_docs)]
+    pub fn encoder_mut(&mut self) -> &mut E {
+        &mut self.encoder
+    }
+
+    #[allow(missing_docs)]
+    pub fn into_inner(self) -> (D, E) {
+        (self.decoder, self.encoder)
+    }
+}
+
+impl<D: Decoder, E> Decoder for PairCodec<D, E> {
+    type Error = D::Error;
+    type Item = D::Item;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decoder.decode(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decoder.decode_eof(src)
+    }
+
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        self.decoder.is_recoverable(err)
+    }
+}
+
+impl<D, E: Encoder> Encoder for PairCodec<D, E> {
+    type Error = E::Error;
+    type Item = E::Item;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encoder.encode(item, dst)
+    }
+
+    fn encode_vectored(&mut self, item: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        self.encoder.encode_vectored(item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{BytesCodec, LengthCodec};
+    use bytes::Bytes;
+
+    #[test]
+    fn forwards_each_direction() {
+        let mut codec = PairCodec::new(LengthCodec::<u8>::new(), BytesCodec);
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from_static(b"\x02hi"), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"\x02hi");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(Bytes::from_static(b"hi")));
+    }
+}
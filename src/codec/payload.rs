@@ -0,0 +1,100 @@
+use super::{Decoder, EncodeBuf, Encoder};
+use bytes::{Bytes, BytesMut};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PayloadCodec<F, C> {
+    frame: F,
+    payload: C,
+}
+
+impl<F, C> PayloadCodec<F, C> {
+    #[allow(missing_docs)]
+    pub const fn new(frame: F, payload: C) -> Self {
+        Self { frame, payload }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PayloadError<F: std::error::Error + 'static, C: std::error::Error + 'static> {
+    #[error("framing error: {0}")]
+    Frame(#[source] F),
+    #[error("payload error: {0}")]
+    Payload(#[source] C),
+    #[error("frame did not contain a complete payload")]
+    Incomplete,
+    #[error("{0} trailing bytes after payload")]
+    Trailing(usize),
+}
+
+impl<F, C> Encoder for PayloadCodec<F, C>
+where
+    F: Encoder<Item = Bytes>,
+    C: Encoder,
+{
+    type Error = PayloadError<F::Error, C::Error>;
+    type Item = C::Item;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut payload = BytesMut::new();
+        self.payload.encode(item, &mut payload).map_err(PayloadError::Payload)?;
+        self.frame.encode(payload.freeze(), dst).map_err(PayloadError::Frame)
+    }
+
+    fn encode_vectored(&mut self, item: Self::Item, dst: &mut EncodeBuf) -> Result<(), Self::Error> {
+        let mut payload = BytesMut::new();
+        self.payload.encode(item, &mut payload).map_err(PayloadError::Payload)?;
+        self.frame
+            .encode_vectored(payload.freeze(), dst)
+            .map_err(PayloadError::Frame)
+    }
+}
+
+impl<F, C> Decoder for PayloadCodec<F, C>
+where
+    F: Decoder<Item = Bytes>,
+    C: Decoder,
+{
+    type Error = PayloadError<F::Error, C::Error>;
+    type Item = C::Item;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.frame.decode(src).map_err(PayloadError::Frame)? {
+            Some(frame) => self.decode_payload(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.frame.decode_eof(src).map_err(PayloadError::Frame)? {
+            Some(frame) => self.decode_payload(frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // A bad payload has already been taken out of the stream with its frame.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        match err {
+            PayloadError::Frame(err) => self.frame.is_recoverable(err),
+            _ => true,
+        }
+    }
+}
+
+impl<F, C> PayloadCodec<F, C>
+where
+    F: Decoder,
+    C: Decoder,
+{
+    fn decode_payload(&mut self, frame: Bytes) -> Result<C::Item, PayloadError<F::Error, C::Error>> {
+        let mut buf = BytesMut::from(&frame[..]);
+        let item = self
+            .payload
+            .decode_eof(&mut buf)
+            .map_err(PayloadError::Payload)?
+            .ok_or(PayloadError::Incomplete)?;
+        if !buf.is_empty() {
+            return Err(PayloadError::Trailing(buf.len()));
+        }
+        Ok(item)
+    }
+}
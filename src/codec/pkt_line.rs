@@ -0,0 +1,257 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+const HEADER_LEN: usize = 4;
+const MAX_PKT_LEN: usize = 65520;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PktLine {
+    Data(Bytes),
+    SideBand(u8, Bytes),
+    Flush,
+    Delim,
+    ResponseEnd,
+}
+
+impl PktLine {
+    #[allow(missing_docs)]
+    pub const MAX_DATA_LEN: usize = MAX_PKT_LEN - HEADER_LEN;
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PktLineCodec {
+    sideband: bool,
+}
+
+impl PktLineCodec {
+    #[allow(missing_docs)]
+    pub const fn new() -> Self {
+        Self { sideband: false }
+    }
+
+    // Decodes data packets as `PktLine::SideBand`, splitting off the side-band-64k channel byte.
+    #[allow(missing_docs)]
+    pub const fn with_sideband() -> Self {
+        Self { sideband: true }
+    }
+
+    #[allow(missing_docs)]
+    pub const fn sideband(&self) -> bool {
+        self.sideband
+    }
+
+    // Side-band framing only starts once the capabilities have been negotiated, so it can be
+    // switched on partway through a stream, typically after the flush-pkt ending a negotiation.
+    #[allow(missing_docs)]
+    pub fn set_sideband(&mut self, sideband: bool) {
+        self.sideband = sideband;
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PktLineError {
+    #[error("invalid pkt-line length {0:?}")]
+    InvalidLength([u8; HEADER_LEN]),
+    #[error("pkt-line of {0} bytes exceeds the maximum of 65520")]
+    TooLong(usize),
+    #[error("invalid side-band channel {0}")]
+    InvalidChannel(u8),
+    #[error("side-band packet is missing its channel byte")]
+    MissingChannel,
+}
+
+fn parse_len(header: [u8; HEADER_LEN]) -> Result<usize, PktLineError> {
+    header.iter().try_fold(0usize, |len, &byte| {
+        let digit = (byte as char).to_digit(16).ok_or(PktLineError::InvalidLength(header))?;
+        Ok(len << 4 | digit as usize)
+    })
+}
+
+impl Encoder for PktLineCodec {
+    type Error = PktLineError;
+    type Item = PktLine;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (channel, data) = match item {
+            PktLine::Flush => {
+                dst.put_slice(b"0000");
+                return Ok(());
+            },
+            PktLine::Delim => {
+                dst.put_slice(b"0001");
+                return Ok(());
+            },
+            PktLine::ResponseEnd => {
+                dst.put_slice(b"0002");
+                return Ok(());
+            },
+            PktLine::Data(data) => (None, data),
+            PktLine::SideBand(channel @ 1 ..= 3, data) => (Some(channel), data),
+            PktLine::SideBand(channel, _) => return Err(PktLineError::InvalidChannel(channel)),
+        };
+
+        let len = HEADER_LEN + usize::from(channel.is_some()) + data.len();
+        if len > MAX_PKT_LEN {
+            return Err(PktLineError::TooLong(len));
+        }
+        dst.reserve(len);
+        dst.put_slice(format!("{:04x}", len).as_bytes());
+        if let Some(channel) = channel {
+            dst.put_u8(channel);
+        }
+        dst.put_slice(&data);
+        Ok(())
+    }
+}
+
+impl Decoder for PktLineCodec {
+    type Error = PktLineError;
+    type Item = PktLine;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let mut header = [0u8; HEADER_LEN];
+        header.copy_from_slice(&src[.. HEADER_LEN]);
+        let len = match parse_len(header) {
+            Ok(len) if len != 3 && len <= MAX_PKT_LEN => len,
+            result => {
+                // The stream cannot be resynchronized after a bad length.
+                src.clear();
+                return Err(match result {
+                    Ok(len) if len > MAX_PKT_LEN => PktLineError::TooLong(len),
+                    Ok(_) => PktLineError::InvalidLength(header),
+                    Err(err) => err,
+                });
+            },
+        };
+
+        let special = match len {
+            0 => Some(PktLine::Flush),
+            1 => Some(PktLine::Delim),
+            2 => Some(PktLine::ResponseEnd),
+            _ => None,
+        };
+        if let Some(pkt) = special {
+            src.advance(HEADER_LEN);
+            return Ok(Some(pkt));
+        }
+
+        if src.len() < len {
+            src.reserve(len - src.len());
+            return Ok(None);
+        }
+        src.advance(HEADER_LEN);
+        let mut data = src.split_to(len - HEADER_LEN).freeze();
+
+        if !self.sideband {
+            return Ok(Some(PktLine::Data(data)));
+        }
+        if data.is_empty() {
+            return Err(PktLineError::MissingChannel);
+        }
+        match data.get_u8() {
+            channel @ 1 ..= 3 => Ok(Some(PktLine::SideBand(channel, data))),
+            channel => Err(PktLineError::InvalidChannel(channel)),
+        }
+    }
+
+    // A packet with a bad channel has been consumed whole, unlike one with a bad length.
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        matches!(err, PktLineError::InvalidChannel(_) | PktLineError::MissingChannel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut codec = PktLineCodec::new();
+        let mut buf = BytesMut::new();
+        let pkts = vec![
+            PktLine::Data(Bytes::from_static(b"command=ls-refs\n")),
+            PktLine::Delim,
+            PktLine::Data(Bytes::new()),
+            PktLine::Flush,
+            PktLine::ResponseEnd,
+        ];
+        for pkt in pkts.clone() {
+            codec.encode(pkt, &mut buf).unwrap();
+        }
+        assert_eq!(&buf[.. 20], b"0014command=ls-refs\n");
+
+        for pkt in pkts {
+            assert_eq!(codec.decode(&mut buf).unwrap(), Some(pkt));
+        }
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn enforces_maximum_length() {
+        let mut codec = PktLineCodec::new();
+        let mut buf = BytesMut::new();
+        codec
+            .encode(PktLine::Data(Bytes::from(vec![0; PktLine::MAX_DATA_LEN])), &mut buf)
+            .unwrap();
+        assert!(matches!(
+            codec.encode(PktLine::Data(Bytes::from(vec![0; PktLine::MAX_DATA_LEN + 1])), &mut buf),
+            Err(PktLineError::TooLong(65521))
+        ));
+
+        let mut buf = BytesMut::from(&b"fff1"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(PktLineError::TooLong(0xfff1))));
+    }
+
+    #[test]
+    fn rejects_invalid_lengths() {
+        let mut codec = PktLineCodec::new();
+        for header in [&b"0003"[..], b"00g4"] {
+            let mut buf = BytesMut::from(header);
+            assert!(matches!(codec.decode(&mut buf), Err(PktLineError::InvalidLength(_))));
+        }
+    }
+
+    #[test]
+    fn demultiplexes_sideband() {
+        let mut codec = PktLineCodec::with_sideband();
+        let mut buf = BytesMut::from(&b"000a\x02hello0009\x01PACK0000"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(PktLine::SideBand(2, Bytes::from_static(b"hello")))
+        );
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(PktLine::SideBand(1, Bytes::from_static(b"PACK")))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(PktLine::Flush));
+
+        let mut buf = BytesMut::from(&b"0006\x07x0005\x01"[..]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(err, PktLineError::InvalidChannel(7)));
+        assert!(codec.is_recoverable(&err));
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(PktLine::SideBand(1, Bytes::new()))
+        );
+    }
+
+    #[test]
+    fn sideband_starts_after_negotiation() {
+        let mut codec = PktLineCodec::new();
+        let mut buf = BytesMut::from(&b"0008ACK\n00000009\x01PACK"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(PktLine::Data(Bytes::from_static(b"ACK\n")))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(PktLine::Flush));
+
+        codec.set_sideband(true);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(PktLine::SideBand(1, Bytes::from_static(b"PACK")))
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+const SSL_REQUEST: i32 = 80_877_103;
+const CANCEL_REQUEST: i32 = 80_877_102;
+const GSSENC_REQUEST: i32 = 80_877_104;
+const DEFAULT_MAX_LEN: usize = 8 << 20;
+// The most buffer space reserved ahead of a message that has not arrived yet, so that a large
+// announced length costs nothing until the peer actually sends it.
+const MAX_RESERVE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgMessage {
+    // The startup packet, with its parameter list left encoded.
+    Startup { version: i32, payload: Bytes },
+    SslRequest,
+    GssEncRequest,
+    CancelRequest { process_id: i32, secret_key: Bytes },
+    // The single unframed `S` or `N` a server sends in reply to an SSL or GSSAPI request.
+    EncryptionResponse(u8),
+    Tagged { tag: u8, payload: Bytes },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PgPhase {
+    // Untagged startup, SSL, GSSAPI and cancel requests from a frontend.
+    Startup,
+    // A single encryption response byte from a backend.
+    EncryptionResponse,
+    // Tagged messages.
+    Normal,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgCodec {
+    phase: PgPhase,
+    max_len: usize,
+}
+
+impl PgCodec {
+    // Decoding a startup packet or an encryption response moves on to the normal phase by
+    // itself, since everything the backend sends afterwards is tagged. Other transitions, such
+    // as waiting on the backend's reply to an SSL request, are made with `set_phase`. Messages
+    // are limited to 8 MiB; use `with_max_len` for larger rows or COPY data.
+    #[allow(missing_docs)]
+    pub const fn new(phase: PgPhase) -> Self {
+        Self::with_max_len(phase, DEFAULT_MAX_LEN)
+    }
+
+    #[allow(missing_docs)]
+    pub const fn with_max_len(phase: PgPhase, max_len: usize) -> Self {
+        Self { phase, max_len }
+    }
+
+    #[allow(missing_docs)]
+    pub fn phase(&self) -> PgPhase {
+        self.phase
+    }
+
+    // Switches phase; anything already buffered is decoded in the new phase.
+    #[allow(missing_docs)]
+    pub fn set_phase(&mut self, phase: PgPhase) {
+        self.phase = phase;
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    // Returns the message length including the length field, once the message is complete.
+    fn frame_len(&self, src: &mut BytesMut, offset: usize, min_len: usize) -> Result<Option<usize>, PgError> {
+        if src.len() < offset + 4 {
+            return Ok(None);
+        }
+        let len = i32::from_be_bytes([src[offset], src[offset + 1], src[offset + 2], src[offset + 3]]);
+        let len = match usize::try_from(len) {
+            Ok(len) if len >= min_len => len,
+            _ => {
+                src.clear();
+                return Err(PgError::InvalidLength(len));
+            },
+        };
+        if len > self.max_len {
+            src.clear();
+            return Err(PgError::TooLong(len));
+        }
+        if src.len() < offset + len {
+            src.reserve((offset + len - src.len()).min(MAX_RESERVE));
+            return Ok(None);
+        }
+        Ok(Some(len))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PgError {
+    #[error("invalid message length {0}")]
+    InvalidLength(i32),
+    #[error("message of {0} bytes exceeds maximum length")]
+    TooLong(usize),
+    #[error("invalid encryption response {0:#04x}")]
+    InvalidEncryptionResponse(u8),
+}
+
+impl Encoder for PgCodec {
+    type Error = PgError;
+    type Item = PgMessage;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (tag, code, payload) = match item {
+            PgMessage::EncryptionResponse(byte) => {
+                dst.put_u8(byte);
+                return Ok(());
+            },
+            PgMessage::Startup { version, payload } => (None, Some(version), payload),
+            PgMessage::SslRequest => (None, Some(SSL_REQUEST), Bytes::new()),
+            PgMessage::GssEncRequest => (None, Some(GSSENC_REQUEST), Bytes::new()),
+            PgMessage::CancelRequest { process_id, secret_key } => {
+                let mut payload = BytesMut::with_capacity(4 + secret_key.len());
+                payload.put_i32(process_id);
+                payload.put_slice(&secret_key);
+                (None, Some(CANCEL_REQUEST), payload.freeze())
+            },
+            PgMessage::Tagged { tag, payload } => (Some(tag), None, payload),
+        };
+
+        let len = 4 + if code.is_some() { 4 } else { 0 } + payload.len();
+        if len > self.max_len {
+            return Err(PgError::TooLong(len));
+        }
+        let len_field = i32::try_from(len).map_err(|_| PgError::TooLong(len))?;
+        dst.reserve(1 + len);
+        if let Some(tag) = tag {
+            dst.put_u8(tag);
+        }
+        dst.put_i32(len_field);
+        if let Some(code) = code {
+            dst.put_i32(code);
+        }
+        dst.put_slice(&payload);
+        Ok(())
+    }
+}
+
+impl Decoder for PgCodec {
+    type Error = PgError;
+    type Item = PgMessage;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.phase {
+            PgPhase::Normal => {
+                let Some(len) = self.frame_len(src, 1, 4)? else {
+                    return Ok(None);
+                };
+                let tag = src.get_u8();
+                src.advance(4);
+                Ok(Some(PgMessage::Tagged {
+                    tag,
+                    payload: src.split_to(len - 4).freeze(),
+                }))
+            },
+            PgPhase::EncryptionResponse => {
+                if src.is_empty() {
+                    return Ok(None);
+                }
+                match src.get_u8() {
+                    byte @ (b'S' | b'N') => {
+                        self.phase = PgPhase::Normal;
+                        Ok(Some(PgMessage::EncryptionResponse(byte)))
+                    },
+                    byte => {
+                        src.clear();
+                        Err(PgError::InvalidEncryptionResponse(byte))
+                    },
+                }
+            },
+            PgPhase::Startup => {
+                let Some(len) = self.frame_len(src, 0, 8)? else {
+                    return Ok(None);
+                };
+                src.advance(4);
+                let code = src.get_i32();
+                let mut payload = src.split_to(len - 8).freeze();
+                Ok(Some(match code {
+                    SSL_REQUEST => PgMessage::SslRequest,
+                    GSSENC_REQUEST => PgMessage::GssEncRequest,
+                    CANCEL_REQUEST if payload.len() >= 4 => PgMessage::CancelRequest {
+                        process_id: payload.get_i32(),
+                        secret_key: payload,
+                    },
+                    CANCEL_REQUEST => {
+                        src.clear();
+                        return Err(PgError::InvalidLength(len as i32));
+                    },
+                    version => {
+                        self.phase = PgPhase::Normal;
+                        PgMessage::Startup { version, payload }
+                    },
+                }))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_then_tagged_messages() {
+        let mut frontend = PgCodec::new(PgPhase::Normal);
+        let mut buf = BytesMut::new();
+        let startup = PgMessage::Startup {
+            version: 196_608,
+            payload: Bytes::from_static(b"user\0postgres\0\0"),
+        };
+        let query = PgMessage::Tagged {
+            tag: b'Q',
+            payload: Bytes::from_static(b"SELECT 1\0"),
+        };
+        frontend.encode(PgMessage::SslRequest, &mut buf).unwrap();
+        frontend.encode(startup.clone(), &mut buf).unwrap();
+        frontend.encode(query.clone(), &mut buf).unwrap();
+        assert_eq!(&buf[.. 8], b"\x00\x00\x00\x08\x04\xd2\x16\x2f");
+
+        // The startup packet and the query arrive in one read, and the switch to the normal
+        // phase must not drop the query.
+        let mut backend = PgCodec::new(PgPhase::Startup);
+        assert_eq!(backend.decode(&mut buf).unwrap(), Some(PgMessage::SslRequest));
+        assert_eq!(backend.phase(), PgPhase::Startup);
+        assert_eq!(backend.decode(&mut buf).unwrap(), Some(startup));
+        assert_eq!(backend.phase(), PgPhase::Normal);
+        assert_eq!(backend.decode(&mut buf).unwrap(), Some(query));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn encryption_response() {
+        let mut codec = PgCodec::new(PgPhase::EncryptionResponse);
+        let mut buf = BytesMut::from(&b"NR\0\0\0\x08\0\0\0\0"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(PgMessage::EncryptionResponse(b'N'))
+        );
+        assert_eq!(codec.phase(), PgPhase::Normal);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(PgMessage::Tagged {
+                tag: b'R',
+                payload: Bytes::from_static(b"\0\0\0\0"),
+            })
+        );
+    }
+
+    #[test]
+    fn cancel_request() {
+        let mut codec = PgCodec::new(PgPhase::Startup);
+        let cancel = PgMessage::CancelRequest {
+            process_id: 42,
+            secret_key: Bytes::from_static(b"\x01\x02\x03\x04"),
+        };
+        let mut buf = BytesMut::new();
+        codec.encode(cancel.clone(), &mut buf).unwrap();
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(cancel));
+    }
+
+    #[test]
+    fn rejects_bad_lengths() {
+        let mut codec = PgCodec::with_max_len(PgPhase::Normal, 1024);
+        let mut buf = BytesMut::from(&b"Q\x00\x00\x00\x03"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(PgError::InvalidLength(3))));
+
+        let mut buf = BytesMut::from(&b"Q\x00\x01\x00\x00"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(PgError::TooLong(65536))));
+
+        let mut buf = BytesMut::from(&b"Q\x00\x00\x00\x08ab"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+
+        let mut codec = PgCodec::new(PgPhase::Normal);
+        let mut buf = BytesMut::from(&b"D\x01\x00\x00\x00"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(PgError::TooLong(0x0100_0000))));
+    }
+
+    #[test]
+    fn huge_lengths_do_not_allocate() {
+        let mut codec = PgCodec::with_max_len(PgPhase::Normal, usize::MAX);
+        let mut buf = BytesMut::from(&b"D\x7f\xff\xff\xff"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.capacity() < 1 << 20);
+    }
+}
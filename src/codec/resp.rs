@@ -0,0 +1,559 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+const MAX_DEPTH: usize = 128;
+const MAX_LINE_LEN: usize = 64 * 1024;
+// The most buffer space reserved ahead of a bulk payload that has not arrived yet.
+const MAX_RESERVE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RespValue {
+    SimpleString(Bytes),
+    Error(Bytes),
+    Integer(i64),
+    BulkString(Bytes),
+    Array(Vec<RespValue>),
+    Null,
+    Map(Vec<(RespValue, RespValue)>),
+    Set(Vec<RespValue>),
+    Push(Vec<RespValue>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(Bytes),
+    // An inline command: space separated arguments on a single line.
+    Inline(Vec<Bytes>),
+}
+
+impl RespValue {
+    // Builds a request array of bulk strings, as sent by clients.
+    #[allow(missing_docs)]
+    pub fn request<I, A>(args: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<Bytes>,
+    {
+        RespValue::Array(args.into_iter().map(|arg| RespValue::BulkString(arg.into())).collect())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RespVersion {
+    Resp2,
+    Resp3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Aggregate {
+    Array,
+    Set,
+    Push,
+    Map,
+}
+
+impl Aggregate {
+    fn finish(self, items: Vec<RespValue>) -> RespValue {
+        match self {
+            Aggregate::Array => RespValue::Array(items),
+            Aggregate::Set => RespValue::Set(items),
+            Aggregate::Push => RespValue::Push(items),
+            Aggregate::Map => {
+                let mut items = items.into_iter();
+                let mut pairs = Vec::with_capacity(items.len() / 2);
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    pairs.push((key, value));
+                }
+                RespValue::Map(pairs)
+            },
+        }
+    }
+}
+
+// An aggregate whose header has been consumed but which is still waiting for elements.
+#[derive(Clone, Debug, PartialEq)]
+struct Pending {
+    kind: Aggregate,
+    remaining: usize,
+    items: Vec<RespValue>,
+}
+
+enum Parsed {
+    Incomplete,
+    Value(RespValue),
+    Aggregate(Aggregate, usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RespCodec {
+    version: RespVersion,
+    max_bulk_len: usize,
+    max_aggregate_len: usize,
+    stack: Vec<Pending>,
+}
+
+impl RespCodec {
+    #[allow(missing_docs)]
+    pub fn new(version: RespVersion) -> Self {
+        Self::with_limits(version, 16 * 1024 * 1024, 1024 * 1024)
+    }
+
+    #[allow(missing_docs)]
+    pub fn with_limits(version: RespVersion, max_bulk_len: usize, max_aggregate_len: usize) -> Self {
+        Self {
+            version,
+            max_bulk_len,
+            max_aggregate_len,
+            stack: Vec::new(),
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn version(&self) -> RespVersion {
+        self.version
+    }
+
+    fn parse_one(&self, src: &mut BytesMut) -> Result<Parsed, RespError> {
+        match src.first() {
+            None => return Ok(Parsed::Incomplete),
+            Some(b'+' | b'-' | b':' | b'$' | b'*' | b'_' | b',' | b'#' | b'(' | b'!' | b'=' | b'%' | b'~' | b'>') => {},
+            Some(_) if self.stack.is_empty() => return self.parse_inline(src),
+            Some(&ty) => return Err(RespError::InvalidType(ty)),
+        }
+        if self.version == RespVersion::Resp2 {
+            if let Some(name) = resp3_type_name(src[0]) {
+                return Err(RespError::Unsupported(name));
+            }
+        }
+
+        let end = match src.windows(2).position(|window| window == b"\r\n") {
+            Some(end) => end,
+            None if src.len() > MAX_LINE_LEN => return Err(RespError::LineTooLong),
+            None => return Ok(Parsed::Incomplete),
+        };
+
+        let line = &src[1 .. end];
+        let parsed = match src[0] {
+            b'+' => Parsed::Value(RespValue::SimpleString(Bytes::copy_from_slice(line))),
+            b'-' => Parsed::Value(RespValue::Error(Bytes::copy_from_slice(line))),
+            b':' => Parsed::Value(RespValue::Integer(parse_int(line)?)),
+            b'(' => Parsed::Value(RespValue::BigNumber(Bytes::copy_from_slice(line))),
+            b'_' if line.is_empty() => Parsed::Value(RespValue::Null),
+            b'#' => match line {
+                b"t" => Parsed::Value(RespValue::Boolean(true)),
+                b"f" => Parsed::Value(RespValue::Boolean(false)),
+                _ => return Err(RespError::Invalid("boolean")),
+            },
+            b',' => std::str::from_utf8(line)
+                .ok()
+                .and_then(|line| line.parse().ok())
+                .map(|double| Parsed::Value(RespValue::Double(double)))
+                .ok_or(RespError::Invalid("double"))?,
+            ty @ (b'$' | b'=' | b'!') => return self.parse_bulk(src, ty, end),
+            ty @ (b'*' | b'~' | b'>' | b'%') => {
+                let kind = match ty {
+                    b'*' => Aggregate::Array,
+                    b'~' => Aggregate::Set,
+                    b'>' => Aggregate::Push,
+                    _ => Aggregate::Map,
+                };
+                let len = parse_int(line)?;
+                src.advance(end + 2);
+                if len == -1 && kind == Aggregate::Array {
+                    return Ok(Parsed::Value(RespValue::Null));
+                }
+                let len = usize::try_from(len).map_err(|_| RespError::Invalid("aggregate length"))?;
+                if len > self.max_aggregate_len {
+                    return Err(RespError::AggregateTooLong(len));
+                }
+                return Ok(Parsed::Aggregate(kind, len));
+            },
+            ty => return Err(RespError::InvalidType(ty)),
+        };
+
+        src.advance(end + 2);
+        Ok(parsed)
+    }
+
+    fn parse_bulk(&self, src: &mut BytesMut, ty: u8, end: usize) -> Result<Parsed, RespError> {
+        let len = parse_int(&src[1 .. end])?;
+        if len == -1 && ty == b'$' {
+            src.advance(end + 2);
+            return Ok(Parsed::Value(RespValue::Null));
+        }
+        let len = usize::try_from(len).map_err(|_| RespError::Invalid("bulk length"))?;
+        if len > self.max_bulk_len {
+            return Err(RespError::BulkTooLong(len));
+        }
+
+        // Leave the header in place until the whole payload has arrived.
+        let frame_len = end + 2 + len + 2;
+        if src.len() < frame_len {
+            src.reserve((frame_len - src.len()).min(MAX_RESERVE));
+            return Ok(Parsed::Incomplete);
+        }
+        if &src[frame_len - 2 .. frame_len] != b"\r\n" {
+            return Err(RespError::Invalid("bulk terminator"));
+        }
+
+        src.advance(end + 2);
+        let data = src.split_to(len).freeze();
+        src.advance(2);
+        Ok(Parsed::Value(match ty {
+            b'!' => RespValue::Error(data),
+            _ => RespValue::BulkString(data),
+        }))
+    }
+
+    fn parse_inline(&self, src: &mut BytesMut) -> Result<Parsed, RespError> {
+        let end = match src.iter().position(|&byte| byte == b'\n') {
+            Some(end) => end,
+            None if src.len() > MAX_LINE_LEN => return Err(RespError::LineTooLong),
+            None => return Ok(Parsed::Incomplete),
+        };
+        let line = src.split_to(end + 1).freeze();
+        let args = line[.. end]
+            .split(|byte| byte.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| line.slice_ref(arg))
+            .collect::<Vec<_>>();
+        if args.len() > self.max_aggregate_len {
+            return Err(RespError::AggregateTooLong(args.len()));
+        }
+        Ok(Parsed::Value(RespValue::Inline(args)))
+    }
+
+    fn decode_value(&mut self, src: &mut BytesMut) -> Result<Option<RespValue>, RespError> {
+        loop {
+            let mut value = match self.parse_one(src)? {
+                Parsed::Incomplete => return Ok(None),
+                Parsed::Value(value) => value,
+                Parsed::Aggregate(kind, 0) => kind.finish(Vec::new()),
+                Parsed::Aggregate(kind, len) => {
+                    if self.stack.len() >= MAX_DEPTH {
+                        return Err(RespError::TooDeep);
+                    }
+                    let remaining = if kind == Aggregate::Map { len * 2 } else { len };
+                    self.stack.push(Pending {
+                        kind,
+                        remaining,
+                        // Don't trust the announced length for preallocation.
+                        items: Vec::with_capacity(remaining.min(64)),
+                    });
+                    continue;
+                },
+            };
+
+            // Fold the completed value into its parents, completing them in turn.
+            loop {
+                let top = match self.stack.last_mut() {
+                    Some(top) => top,
+                    None => return Ok(Some(value)),
+                };
+                top.items.push(value);
+                top.remaining -= 1;
+                if top.remaining > 0 {
+                    break;
+                }
+                let done = self.stack.pop().expect("stack is not empty");
+                value = done.kind.finish(done.items);
+            }
+        }
+    }
+
+    // Inline commands are a top-level form only, so `nested` rejects them inside aggregates.
+    fn encode_value(&self, value: RespValue, dst: &mut BytesMut, nested: bool) -> Result<(), RespError> {
+        fn header(dst: &mut BytesMut, ty: u8, len: impl std::fmt::Display) {
+            dst.put_u8(ty);
+            dst.put_slice(len.to_string().as_bytes());
+            dst.put_slice(b"\r\n");
+        }
+
+        fn line(dst: &mut BytesMut, ty: u8, data: &[u8]) -> Result<(), RespError> {
+            if data.iter().any(|&byte| byte == b'\r' || byte == b'\n') {
+                return Err(RespError::Invalid("line contains CR or LF"));
+            }
+            dst.put_u8(ty);
+            dst.put_slice(data);
+            dst.put_slice(b"\r\n");
+            Ok(())
+        }
+
+        let resp3 = |name| match self.version {
+            RespVersion::Resp2 => Err(RespError::Unsupported(name)),
+            RespVersion::Resp3 => Ok(()),
+        };
+
+        match value {
+            RespValue::SimpleString(data) => line(dst, b'+', &data)?,
+            RespValue::Error(data) => line(dst, b'-', &data)?,
+            RespValue::Integer(int) => header(dst, b':', int),
+            RespValue::BulkString(data) => {
+                header(dst, b'$', data.len());
+                dst.put_slice(&data);
+                dst.put_slice(b"\r\n");
+            },
+            RespValue::Null => match self.version {
+                RespVersion::Resp2 => dst.put_slice(b"$-1\r\n"),
+                RespVersion::Resp3 => dst.put_slice(b"_\r\n"),
+            },
+            RespValue::Array(items) => {
+                header(dst, b'*', items.len());
+                for item in items {
+                    self.encode_value(item, dst, true)?;
+                }
+            },
+            RespValue::Set(items) => {
+                resp3("set")?;
+                header(dst, b'~', items.len());
+                for item in items {
+                    self.encode_value(item, dst, true)?;
+                }
+            },
+            RespValue::Push(items) => {
+                resp3("push")?;
+                header(dst, b'>', items.len());
+                for item in items {
+                    self.encode_value(item, dst, true)?;
+                }
+            },
+            RespValue::Map(pairs) => {
+                resp3("map")?;
+                header(dst, b'%', pairs.len());
+                for (key, value) in pairs {
+                    self.encode_value(key, dst, true)?;
+                    self.encode_value(value, dst, true)?;
+                }
+            },
+            RespValue::Double(double) => {
+                resp3("double")?;
+                let text = match double {
+                    double if double.is_nan() => "nan".to_owned(),
+                    double if double == f64::INFINITY => "inf".to_owned(),
+                    double if double == f64::NEG_INFINITY => "-inf".to_owned(),
+                    double => double.to_string(),
+                };
+                line(dst, b',', text.as_bytes())?;
+            },
+            RespValue::Boolean(boolean) => {
+                resp3("boolean")?;
+                line(dst, b'#', if boolean { b"t" } else { b"f" })?;
+            },
+            RespValue::BigNumber(data) => {
+                resp3("big number")?;
+                line(dst, b'(', &data)?;
+            },
+            RespValue::Inline(_) if nested => return Err(RespError::Invalid("nested inline command")),
+            RespValue::Inline(args) => {
+                for (i, arg) in args.iter().enumerate() {
+                    if arg.is_empty() || arg.iter().any(u8::is_ascii_whitespace) {
+                        return Err(RespError::Invalid("inline argument"));
+                    }
+                    if i > 0 {
+                        dst.put_u8(b' ');
+                    }
+                    dst.put_slice(arg);
+                }
+                dst.put_slice(b"\r\n");
+            },
+        }
+        Ok(())
+    }
+}
+
+impl Default for RespCodec {
+    fn default() -> Self {
+        Self::new(RespVersion::Resp2)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RespError {
+    #[error("invalid RESP type byte {0:#x}")]
+    InvalidType(u8),
+    #[error("invalid RESP {0}")]
+    Invalid(&'static str),
+    #[error("bulk string of {0} bytes exceeds limit")]
+    BulkTooLong(usize),
+    #[error("aggregate of {0} elements exceeds limit")]
+    AggregateTooLong(usize),
+    #[error("aggregates nested too deeply")]
+    TooDeep,
+    #[error("line exceeds limit")]
+    LineTooLong,
+    #[error("{0} is not supported by RESP2")]
+    Unsupported(&'static str),
+}
+
+fn resp3_type_name(ty: u8) -> Option<&'static str> {
+    Some(match ty {
+        b'_' => "null",
+        b',' => "double",
+        b'#' => "boolean",
+        b'(' => "big number",
+        b'!' => "bulk error",
+        b'=' => "verbatim string",
+        b'%' => "map",
+        b'~' => "set",
+        b'>' => "push",
+        _ => return None,
+    })
+}
+
+fn parse_int(line: &[u8]) -> Result<i64, RespError> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|line| line.parse().ok())
+        .ok_or(RespError::Invalid("integer"))
+}
+
+impl Encoder for RespCodec {
+    type Error = RespError;
+    type Item = RespValue;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        // Encode into a scratch buffer so a failure doesn't leave half a value behind.
+        let mut buf = BytesMut::new();
+        self.encode_value(item, &mut buf, false)?;
+        dst.unsplit(buf);
+        Ok(())
+    }
+}
+
+impl Decoder for RespCodec {
+    type Error = RespError;
+    type Item = RespValue;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_value(src).inspect_err(|_| {
+            // Protocol errors are not recoverable; drop the partial state.
+            self.stack.clear();
+            src.clear();
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(codec: &mut RespCodec, input: &[u8]) -> Vec<RespValue> {
+        let mut buf = BytesMut::from(input);
+        let mut values = Vec::new();
+        while let Some(value) = codec.decode(&mut buf).unwrap() {
+            values.push(value);
+        }
+        values
+    }
+
+    #[test]
+    fn decodes_resp2_types() {
+        let mut codec = RespCodec::default();
+        let values = decode_all(&mut codec, b"+OK\r\n-ERR bad\r\n:-42\r\n$5\r\nhello\r\n$-1\r\n*-1\r\n");
+        assert_eq!(values, vec![
+            RespValue::SimpleString(Bytes::from_static(b"OK")),
+            RespValue::Error(Bytes::from_static(b"ERR bad")),
+            RespValue::Integer(-42),
+            RespValue::BulkString(Bytes::from_static(b"hello")),
+            RespValue::Null,
+            RespValue::Null,
+        ]);
+    }
+
+    #[test]
+    fn decodes_resp3_types() {
+        let mut codec = RespCodec::new(RespVersion::Resp3);
+        let values = decode_all(&mut codec, b"%1\r\n+key\r\n~2\r\n#t\r\n,1.5\r\n>1\r\n_\r\n");
+        assert_eq!(values, vec![
+            RespValue::Map(vec![(
+                RespValue::SimpleString(Bytes::from_static(b"key")),
+                RespValue::Set(vec![RespValue::Boolean(true), RespValue::Double(1.5)]),
+            )]),
+            RespValue::Push(vec![RespValue::Null]),
+        ]);
+    }
+
+    #[test]
+    fn nested_aggregates_in_fragments() {
+        let input = b"*2\r\n*2\r\n:1\r\n$3\r\nfoo\r\n*1\r\n*0\r\n";
+        let mut codec = RespCodec::default();
+        let mut buf = BytesMut::new();
+        let mut decoded = None;
+        for &byte in &input[..] {
+            assert!(decoded.is_none());
+            buf.put_u8(byte);
+            decoded = codec.decode(&mut buf).unwrap();
+        }
+        assert_eq!(
+            decoded,
+            Some(RespValue::Array(vec![
+                RespValue::Array(vec![
+                    RespValue::Integer(1),
+                    RespValue::BulkString(Bytes::from_static(b"foo"))
+                ]),
+                RespValue::Array(vec![RespValue::Array(vec![])]),
+            ]))
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn roundtrip_requests() {
+        let mut codec = RespCodec::default();
+        let mut buf = BytesMut::new();
+        let request = RespValue::request(vec!["SET", "key", "value"]);
+        codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(request));
+
+        let inline = RespValue::Inline(vec![Bytes::from_static(b"PING"), Bytes::from_static(b"hi")]);
+        codec.encode(inline.clone(), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"PING hi\r\n");
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(inline));
+    }
+
+    #[test]
+    fn resp2_rejects_resp3_types() {
+        let mut codec = RespCodec::default();
+        let mut buf = BytesMut::new();
+        assert!(matches!(
+            codec.encode(RespValue::Boolean(true), &mut buf),
+            Err(RespError::Unsupported("boolean"))
+        ));
+        assert!(buf.is_empty());
+
+        let mut buf = BytesMut::from(&b"%1\r\n+a\r\n:1\r\n"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(RespError::Unsupported("map"))));
+        let mut buf = BytesMut::from(&b"*1\r\n#t\r\n"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(RespError::Unsupported("boolean"))));
+    }
+
+    #[test]
+    fn rejects_nested_inline_commands() {
+        let mut codec = RespCodec::default();
+        let mut buf = BytesMut::new();
+        let nested = RespValue::Array(vec![RespValue::Inline(vec![Bytes::from_static(b"PING")])]);
+        assert!(matches!(
+            codec.encode(nested, &mut buf),
+            Err(RespError::Invalid("nested inline command"))
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn large_bulk_headers_reserve_little() {
+        let mut codec = RespCodec::default();
+        let mut buf = BytesMut::from(&b"$16000000\r\n"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.capacity() < 1 << 20);
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let mut codec = RespCodec::with_limits(RespVersion::Resp2, 4, 2);
+        let mut buf = BytesMut::from(&b"$5\r\n"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(RespError::BulkTooLong(5))));
+        let mut buf = BytesMut::from(&b"*3\r\n"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(RespError::AggregateTooLong(3))));
+        let mut buf = BytesMut::from(&b"*1\r\n".repeat(MAX_DEPTH + 1)[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(RespError::TooDeep)));
+    }
+}
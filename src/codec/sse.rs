@@ -0,0 +1,309 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, BytesMut};
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+const DEFAULT_MAX_LEN: usize = 1 << 20;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    // `None` stands for the default `message` type.
+    pub event: Option<String>,
+    pub data: String,
+    pub id: Option<String>,
+    pub retry: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SseCodec {
+    max_len: usize,
+    started: bool,
+    // Set after a line ended in CR, so that the LF of a CRLF split across reads is skipped.
+    skip_lf: bool,
+    event: Option<String>,
+    data: String,
+    last_id: String,
+    retry: Option<u64>,
+    reconnection_time: Option<u64>,
+}
+
+impl SseCodec {
+    #[allow(missing_docs)]
+    pub const fn new() -> Self {
+        Self::with_max_len(DEFAULT_MAX_LEN)
+    }
+
+    // Bounds the size of a single line and of the data accumulated for one event.
+    #[allow(missing_docs)]
+    pub const fn with_max_len(max_len: usize) -> Self {
+        Self {
+            max_len,
+            started: false,
+            skip_lf: false,
+            event: None,
+            data: String::new(),
+            last_id: String::new(),
+            retry: None,
+            reconnection_time: None,
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    // The last event ID seen on the stream, to be sent back as `Last-Event-ID` on reconnection.
+    #[allow(missing_docs)]
+    pub fn last_event_id(&self) -> &str {
+        &self.last_id
+    }
+
+    // The latest reconnection time the server asked for, which applies whether or not the block
+    // carrying it dispatched an event.
+    #[allow(missing_docs)]
+    pub fn reconnection_time(&self) -> Option<u64> {
+        self.reconnection_time
+    }
+
+    fn process_line(&mut self, line: &str) {
+        if line.starts_with(':') {
+            return;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            // An empty type stands for the default, like no `event` field at all.
+            "event" => self.event = Some(value.to_owned()).filter(|event| !event.is_empty()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            },
+            "id" if !value.contains('\0') => value.clone_into(&mut self.last_id),
+            "retry" if !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit()) => {
+                self.retry = value.parse().ok();
+                self.reconnection_time = self.retry.or(self.reconnection_time);
+            },
+            _ => {},
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        let retry = self.retry.take();
+        let mut data = std::mem::take(&mut self.data);
+        // Blocks without data are not dispatched; a reconnection time they carry is still kept
+        // by `reconnection_time`.
+        if data.is_empty() {
+            return None;
+        }
+        data.pop();
+        Some(SseEvent {
+            event,
+            data,
+            id: Some(self.last_id.clone()).filter(|id| !id.is_empty()),
+            retry,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.event = None;
+        self.data.clear();
+        self.retry = None;
+    }
+}
+
+impl Default for SseCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SseError {
+    #[error("event exceeds maximum length")]
+    TooLong,
+    #[error("invalid {0} field")]
+    InvalidField(&'static str),
+}
+
+fn check_field(name: &'static str, value: &str) -> Result<(), SseError> {
+    if value.contains(['\r', '\n']) || (name == "id" && value.contains('\0')) {
+        return Err(SseError::InvalidField(name));
+    }
+    Ok(())
+}
+
+impl Encoder for SseCodec {
+    type Error = SseError;
+    type Item = SseEvent;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut out = String::new();
+        if let Some(event) = &item.event {
+            check_field("event", event)?;
+            out.push_str("event: ");
+            out.push_str(event);
+            out.push('\n');
+        }
+        if let Some(id) = &item.id {
+            check_field("id", id)?;
+            out.push_str("id: ");
+            out.push_str(id);
+            out.push('\n');
+        }
+        if let Some(retry) = item.retry {
+            out.push_str(&format!("retry: {}\n", retry));
+        }
+        // Every line of the payload gets its own `data` field, whatever its line ending.
+        let data = item.data.replace("\r\n", "\n").replace('\r', "\n");
+        for line in data.split('\n') {
+            out.push_str("data: ");
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push('\n');
+        dst.put_slice(out.as_bytes());
+        Ok(())
+    }
+}
+
+impl Decoder for SseCodec {
+    type Error = SseError;
+    type Item = SseEvent;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if !self.started {
+            if src.len() < BOM.len() && BOM.starts_with(src) {
+                return Ok(None);
+            }
+            if src.starts_with(BOM) {
+                src.advance(BOM.len());
+            }
+            self.started = true;
+        }
+
+        loop {
+            if self.skip_lf && !src.is_empty() {
+                if src[0] == b'\n' {
+                    src.advance(1);
+                }
+                self.skip_lf = false;
+            }
+
+            let end = match src.iter().position(|&byte| byte == b'\r' || byte == b'\n') {
+                Some(end) => end,
+                None if src.len() > self.max_len => {
+                    src.clear();
+                    self.reset();
+                    return Err(SseError::TooLong);
+                },
+                None => return Ok(None),
+            };
+            let line = src.split_to(end);
+            self.skip_lf = src[0] == b'\r';
+            src.advance(1);
+
+            if line.is_empty() {
+                match self.dispatch() {
+                    Some(event) => return Ok(Some(event)),
+                    None => continue,
+                }
+            }
+            self.process_line(&String::from_utf8_lossy(&line));
+            if self.data.len() > self.max_len {
+                self.reset();
+                return Err(SseError::TooLong);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(codec: &mut SseCodec, buf: &mut BytesMut) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        while let Some(event) = codec.decode(buf).unwrap() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut codec = SseCodec::new();
+        let event = SseEvent {
+            event: Some("update".into()),
+            data: "line one\nline two".into(),
+            id: Some("42".into()),
+            retry: Some(3000),
+        };
+        let mut buf = BytesMut::new();
+        codec.encode(event.clone(), &mut buf).unwrap();
+        assert_eq!(
+            &buf[..],
+            &b"event: update\nid: 42\nretry: 3000\ndata: line one\ndata: line two\n\n"[..]
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(event));
+    }
+
+    #[test]
+    fn follows_whatwg_parsing_rules() {
+        let mut codec = SseCodec::new();
+        let mut buf = BytesMut::from(
+            &b"\xef\xbb\xbf: comment\r\ndata:YHOO\rdata: +2\r\ndata\n\nid: 1\nevent: add\nunknown: x\n\n"[..],
+        );
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![SseEvent {
+            data: "YHOO\n+2\n".into(),
+            ..Default::default()
+        }]);
+        assert_eq!(codec.last_event_id(), "1");
+
+        buf.extend_from_slice(b"data: after\n\n");
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![SseEvent {
+            data: "after".into(),
+            id: Some("1".into()),
+            ..Default::default()
+        }]);
+    }
+
+    #[test]
+    fn crlf_split_across_reads() {
+        let mut codec = SseCodec::new();
+        let mut buf = BytesMut::from(&b"data: a\r"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"\n\r\n");
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![SseEvent {
+            data: "a".into(),
+            ..Default::default()
+        }]);
+    }
+
+    #[test]
+    fn retry_only_blocks_are_not_events() {
+        let mut codec = SseCodec::new();
+        let mut buf = BytesMut::from(&b"retry: 5000\n\nevent:\ndata: x\n\n"[..]);
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![SseEvent {
+            data: "x".into(),
+            ..Default::default()
+        }]);
+        assert_eq!(codec.reconnection_time(), Some(5000));
+    }
+
+    #[test]
+    fn enforces_maximum_length() {
+        let mut codec = SseCodec::with_max_len(8);
+        let mut buf = BytesMut::from(&b"data: 0123456789"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(SseError::TooLong)));
+
+        let mut buf = BytesMut::from(&b"data: 01234\ndata: 56789\n\n"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(SseError::TooLong)));
+
+        let mut codec = SseCodec::new();
+        let mut buf = BytesMut::from(&vec![b'x'; DEFAULT_MAX_LEN + 1][..]);
+        assert!(matches!(codec.decode(&mut buf), Err(SseError::TooLong)));
+    }
+}
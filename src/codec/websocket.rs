@@ -0,0 +1,465 @@
+use super::{Decoder, Encoder};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+const MAX_CONTROL_LEN: usize = 125;
+// The most buffer space reserved ahead of a payload that has not arrived yet, so that a large
+// announced length costs nothing until the peer actually sends it.
+const MAX_RESERVE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    fn from_bits(bits: u8) -> Result<Self, WebSocketError> {
+        match bits {
+            0x0 => Ok(Opcode::Continuation),
+            0x1 => Ok(Opcode::Text),
+            0x2 => Ok(Opcode::Binary),
+            0x8 => Ok(Opcode::Close),
+            0x9 => Ok(Opcode::Ping),
+            0xa => Ok(Opcode::Pong),
+            bits => Err(WebSocketError::ReservedOpcode(bits)),
+        }
+    }
+
+    const fn bits(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0,
+            Opcode::Text => 0x1,
+            Opcode::Binary => 0x2,
+            Opcode::Close => 0x8,
+            Opcode::Ping => 0x9,
+            Opcode::Pong => 0xa,
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub const fn is_control(self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WebSocketFrame {
+    pub fin: bool,
+    // The three RSV bits, whose meaning is up to negotiated extensions.
+    pub rsv: u8,
+    pub opcode: Opcode,
+    pub payload: Bytes,
+}
+
+impl WebSocketFrame {
+    #[allow(missing_docs)]
+    pub fn new(opcode: Opcode, payload: impl Into<Bytes>) -> Self {
+        Self {
+            fin: true,
+            rsv: 0,
+            opcode,
+            payload: payload.into(),
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::new(Opcode::Text, text.into())
+    }
+
+    #[allow(missing_docs)]
+    pub fn binary(data: impl Into<Bytes>) -> Self {
+        Self::new(Opcode::Binary, data)
+    }
+
+    #[allow(missing_docs)]
+    pub fn ping(data: impl Into<Bytes>) -> Self {
+        Self::new(Opcode::Ping, data)
+    }
+
+    #[allow(missing_docs)]
+    pub fn pong(data: impl Into<Bytes>) -> Self {
+        Self::new(Opcode::Pong, data)
+    }
+
+    #[allow(missing_docs)]
+    pub fn close(code: u16, reason: &str) -> Self {
+        let mut payload = BytesMut::with_capacity(2 + reason.len());
+        payload.put_u16(code);
+        payload.put_slice(reason.as_bytes());
+        Self::new(Opcode::Close, payload.freeze())
+    }
+}
+
+// Clients mask every frame they send and servers never do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+#[derive(Clone, Debug)]
+pub struct WebSocketCodec {
+    role: Role,
+    max_payload_len: usize,
+    reassemble: bool,
+    // The first frame of the data message currently being fragmented, if any.
+    fragment: Option<(u8, Opcode)>,
+    message: BytesMut,
+}
+
+impl WebSocketCodec {
+    #[allow(missing_docs)]
+    pub fn new(role: Role) -> Self {
+        Self::with_options(role, 64 << 20, false)
+    }
+
+    // With `reassemble` set, continuation frames are collected and each data message is yielded
+    // as a single frame with `fin` set. Control frames sent between fragments are still yielded
+    // as they arrive. `max_payload_len` then also bounds the size of the reassembled message.
+    #[allow(missing_docs)]
+    pub fn with_options(role: Role, max_payload_len: usize, reassemble: bool) -> Self {
+        Self {
+            role,
+            max_payload_len,
+            reassemble,
+            fragment: None,
+            message: BytesMut::new(),
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    #[allow(missing_docs)]
+    pub fn max_payload_len(&self) -> usize {
+        self.max_payload_len
+    }
+
+    // RFC 6455 requires masking keys from a strong source of entropy.
+    fn mask_key() -> Result<[u8; 4], WebSocketError> {
+        let mut mask = [0; 4];
+        getrandom::getrandom(&mut mask).map_err(|_| WebSocketError::MaskKey)?;
+        Ok(mask)
+    }
+
+    // Tracks fragmentation state, returning the frame to yield if any.
+    fn accept(&mut self, frame: WebSocketFrame) -> Result<Option<WebSocketFrame>, WebSocketError> {
+        if frame.opcode.is_control() {
+            return Ok(Some(frame));
+        }
+        let first = match (frame.opcode, self.fragment) {
+            (Opcode::Continuation, None) => return Err(WebSocketError::UnexpectedContinuation),
+            (Opcode::Continuation, Some(first)) => first,
+            (_, Some(_)) => return Err(WebSocketError::ExpectedContinuation),
+            (opcode, None) => (frame.rsv, opcode),
+        };
+        self.fragment = if frame.fin { None } else { Some(first) };
+
+        if !self.reassemble {
+            return Ok(Some(frame));
+        }
+        if frame.fin && self.message.is_empty() && frame.opcode != Opcode::Continuation {
+            return Ok(Some(frame));
+        }
+        let len = self.message.len() + frame.payload.len();
+        if len > self.max_payload_len {
+            self.fragment = None;
+            self.message.clear();
+            return Err(WebSocketError::PayloadTooLong(len as u64));
+        }
+        self.message.extend_from_slice(&frame.payload);
+        if !frame.fin {
+            return Ok(None);
+        }
+        Ok(Some(WebSocketFrame {
+            fin: true,
+            rsv: first.0,
+            opcode: first.1,
+            payload: self.message.split().freeze(),
+        }))
+    }
+
+    fn decode_frame(&mut self, src: &mut BytesMut) -> Result<Option<WebSocketFrame>, WebSocketError> {
+        if src.len() < 2 {
+            return Ok(None);
+        }
+        let (fin, rsv, opcode) = (
+            src[0] & 0x80 != 0,
+            (src[0] >> 4) & 0x7,
+            Opcode::from_bits(src[0] & 0xf)?,
+        );
+        let masked = src[1] & 0x80 != 0;
+        match (self.role, masked) {
+            (Role::Server, false) => return Err(WebSocketError::UnmaskedFrame),
+            (Role::Client, true) => return Err(WebSocketError::MaskedFrame),
+            _ => {},
+        }
+
+        let (len, len_size) = match src[1] & 0x7f {
+            126 if src.len() < 4 => return Ok(None),
+            126 => (u64::from(u16::from_be_bytes([src[2], src[3]])), 2),
+            127 if src.len() < 10 => return Ok(None),
+            127 => {
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&src[2 .. 10]);
+                (u64::from_be_bytes(len), 8)
+            },
+            len => (u64::from(len), 0),
+        };
+        let minimal = match len_size {
+            2 => len >= 126,
+            8 => len > u64::from(u16::MAX) && len >> 63 == 0,
+            _ => true,
+        };
+        if !minimal {
+            return Err(WebSocketError::InvalidLength);
+        }
+        if opcode.is_control() && (len > MAX_CONTROL_LEN as u64 || !fin) {
+            return Err(WebSocketError::InvalidControlFrame);
+        }
+        if opcode == Opcode::Close && len == 1 {
+            return Err(WebSocketError::InvalidClose);
+        }
+        if len > self.max_payload_len as u64 {
+            return Err(WebSocketError::PayloadTooLong(len));
+        }
+
+        let len = len as usize;
+        let header_len = 2 + len_size + if masked { 4 } else { 0 };
+        if src.len() < header_len + len {
+            src.reserve((header_len + len - src.len()).min(MAX_RESERVE));
+            return Ok(None);
+        }
+        let mut mask = [0u8; 4];
+        if masked {
+            mask.copy_from_slice(&src[header_len - 4 .. header_len]);
+        }
+        src.advance(header_len);
+        let mut payload = src.split_to(len);
+        if masked {
+            apply_mask(&mut payload, mask);
+        }
+        Ok(Some(WebSocketFrame {
+            fin,
+            rsv,
+            opcode,
+            payload: payload.freeze(),
+        }))
+    }
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebSocketError {
+    #[error("reserved opcode {0:#x}")]
+    ReservedOpcode(u8),
+    #[error("control frames must be unfragmented and at most 125 bytes")]
+    InvalidControlFrame,
+    #[error("payload length is not minimally encoded")]
+    InvalidLength,
+    #[error("payload of {0} bytes exceeds maximum length")]
+    PayloadTooLong(u64),
+    #[error("client frame is not masked")]
+    UnmaskedFrame,
+    #[error("server frame is masked")]
+    MaskedFrame,
+    #[error("continuation frame without a fragmented message")]
+    UnexpectedContinuation,
+    #[error("new data frame while a fragmented message is in progress")]
+    ExpectedContinuation,
+    #[error("close frame payload too short for a status code")]
+    InvalidClose,
+    #[error("RSV bits out of range")]
+    InvalidRsv,
+    #[error("failed to generate a masking key")]
+    MaskKey,
+}
+
+impl Encoder for WebSocketCodec {
+    type Error = WebSocketError;
+    type Item = WebSocketFrame;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let len = item.payload.len();
+        if item.rsv > 0x7 {
+            return Err(WebSocketError::InvalidRsv);
+        }
+        if item.opcode.is_control() && (len > MAX_CONTROL_LEN || !item.fin) {
+            return Err(WebSocketError::InvalidControlFrame);
+        }
+        // A close payload, if any, starts with a two-byte status code.
+        if item.opcode == Opcode::Close && len == 1 {
+            return Err(WebSocketError::InvalidClose);
+        }
+        if len > self.max_payload_len {
+            return Err(WebSocketError::PayloadTooLong(len as u64));
+        }
+
+        let mask = match self.role {
+            Role::Client => Some(Self::mask_key()?),
+            Role::Server => None,
+        };
+        let masked = mask.is_some();
+        dst.reserve(14 + len);
+        dst.put_u8(u8::from(item.fin) << 7 | item.rsv << 4 | item.opcode.bits());
+        let mask_bit = u8::from(masked) << 7;
+        if len < 126 {
+            dst.put_u8(mask_bit | len as u8);
+        } else if let Ok(len) = u16::try_from(len) {
+            dst.put_u8(mask_bit | 126);
+            dst.put_u16(len);
+        } else {
+            dst.put_u8(mask_bit | 127);
+            dst.put_u64(len as u64);
+        }
+
+        if let Some(mask) = mask {
+            dst.put_slice(&mask);
+            let start = dst.len();
+            dst.put_slice(&item.payload);
+            apply_mask(&mut dst[start ..], mask);
+        } else {
+            dst.put_slice(&item.payload);
+        }
+        Ok(())
+    }
+}
+
+impl Decoder for WebSocketCodec {
+    type Error = WebSocketError;
+    type Item = WebSocketFrame;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let frame = match self.decode_frame(src) {
+                Ok(Some(frame)) => self.accept(frame),
+                Ok(None) => return Ok(None),
+                Err(err) => Err(err),
+            };
+            match frame {
+                Ok(Some(frame)) => return Ok(Some(frame)),
+                Ok(None) => continue,
+                Err(err) => {
+                    // Protocol violations are fatal to a WebSocket connection.
+                    src.clear();
+                    return Err(err);
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_to_server_roundtrip() {
+        let mut client = WebSocketCodec::new(Role::Client);
+        let mut server = WebSocketCodec::new(Role::Server);
+        let mut buf = BytesMut::new();
+        for frame in [
+            WebSocketFrame::text("Hello"),
+            WebSocketFrame::binary(vec![0xaa; 300]),
+            WebSocketFrame::binary(vec![0xbb; 70000]),
+            WebSocketFrame::close(1000, "bye"),
+        ] {
+            client.encode(frame.clone(), &mut buf).unwrap();
+            assert_eq!(buf[1] & 0x80, 0x80);
+            assert_eq!(server.decode(&mut buf).unwrap(), Some(frame));
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn decodes_rfc_examples() {
+        let mut client = WebSocketCodec::new(Role::Client);
+        let mut buf = BytesMut::from(&b"\x81\x05Hello"[..]);
+        assert_eq!(client.decode(&mut buf).unwrap(), Some(WebSocketFrame::text("Hello")));
+
+        let mut server = WebSocketCodec::new(Role::Server);
+        let mut buf = BytesMut::from(&b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58"[..]);
+        assert_eq!(server.decode(&mut buf).unwrap(), Some(WebSocketFrame::text("Hello")));
+
+        let mut buf = BytesMut::from(&b"\x81\x05Hello"[..]);
+        assert!(matches!(server.decode(&mut buf), Err(WebSocketError::UnmaskedFrame)));
+    }
+
+    #[test]
+    fn enforces_control_frame_rules() {
+        let mut codec = WebSocketCodec::new(Role::Server);
+        let mut buf = BytesMut::new();
+        assert!(matches!(
+            codec.encode(WebSocketFrame::ping(vec![0; 126]), &mut buf),
+            Err(WebSocketError::InvalidControlFrame)
+        ));
+
+        let mut client = WebSocketCodec::new(Role::Client);
+        let mut buf = BytesMut::from(&b"\x09\x00"[..]);
+        assert!(matches!(
+            client.decode(&mut buf),
+            Err(WebSocketError::InvalidControlFrame)
+        ));
+
+        let mut buf = BytesMut::from(&b"\x88\x01\x03"[..]);
+        assert!(matches!(client.decode(&mut buf), Err(WebSocketError::InvalidClose)));
+        let mut buf = BytesMut::new();
+        let close = WebSocketFrame::new(Opcode::Close, Bytes::from_static(b"\x03"));
+        assert!(matches!(
+            codec.encode(close, &mut buf),
+            Err(WebSocketError::InvalidClose)
+        ));
+    }
+
+    #[test]
+    fn huge_lengths_do_not_allocate() {
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf = BytesMut::from(&b"\x82\x7f\x00\x00\x00\x00\x03\xff\xff\xff"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.capacity() < 1 << 20);
+    }
+
+    #[test]
+    fn reassembles_fragmented_messages() {
+        let mut codec = WebSocketCodec::with_options(Role::Client, 1024, true);
+        let mut buf = BytesMut::from(&b"\x01\x03Hel\x89\x00\x80\x02lo"[..]);
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(WebSocketFrame::ping(Bytes::new()))
+        );
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(WebSocketFrame::text("Hello")));
+
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf = BytesMut::from(&b"\x01\x03Hel\x80\x02lo"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap().map(|frame| frame.fin), Some(false));
+        assert_eq!(
+            codec.decode(&mut buf).unwrap().map(|frame| frame.opcode),
+            Some(Opcode::Continuation)
+        );
+
+        let mut buf = BytesMut::from(&b"\x80\x02lo"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(WebSocketError::UnexpectedContinuation)
+        ));
+    }
+
+    #[test]
+    fn rejects_non_minimal_lengths() {
+        let mut codec = WebSocketCodec::new(Role::Client);
+        let mut buf = BytesMut::from(&b"\x82\x7e\x00\x05hello"[..]);
+        assert!(matches!(codec.decode(&mut buf), Err(WebSocketError::InvalidLength)));
+    }
+}
@@ -0,0 +1,63 @@
+use super::inner::{BACKPRESSURE_BOUNDARY, INITIAL_CAPACITY, MAX_READ_SIZE};
+
+// Buffer sizing shared by `Framed`, `FramedRead` and `FramedWrite`. Small values suit
+// latency-sensitive connections, large ones bulk transfers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FramedConfig {
+    pub(super) read_capacity: usize,
+    pub(super) write_capacity: usize,
+    pub(super) write_high_water_mark: usize,
+    pub(super) max_read_size: usize,
+    pub(super) resume_after_errors: bool,
+}
+
+impl FramedConfig {
+    pub const fn new() -> Self {
+        Self {
+            read_capacity: INITIAL_CAPACITY,
+            write_capacity: INITIAL_CAPACITY,
+            write_high_water_mark: BACKPRESSURE_BOUNDARY,
+            max_read_size: MAX_READ_SIZE,
+            resume_after_errors: false,
+        }
+    }
+
+    pub const fn read_capacity(mut self, capacity: usize) -> Self {
+        self.read_capacity = capacity;
+        self
+    }
+
+    pub const fn write_capacity(mut self, capacity: usize) -> Self {
+        self.write_capacity = capacity;
+        self
+    }
+
+    // `poll_ready` writes buffered frames out until fewer than this many bytes remain, so a
+    // low mark sends each frame promptly and a high one batches many frames per write.
+    pub const fn write_high_water_mark(mut self, high_water_mark: usize) -> Self {
+        self.write_high_water_mark = high_water_mark;
+        self
+    }
+
+    // The largest single read into the read buffer. Read sizes adapt between a small floor and
+    // this limit.
+    pub const fn max_read_size(mut self, max_read_size: usize) -> Self {
+        // A zero-sized read would look like end of file.
+        self.max_read_size = if max_read_size == 0 { 1 } else { max_read_size };
+        self
+    }
+
+    // By default the stream yields `None` after any decode error. With this set, errors the
+    // decoder reports as recoverable through `Decoder::is_recoverable` are yielded and decoding
+    // resumes on the next poll.
+    pub const fn resume_after_errors(mut self, resume: bool) -> Self {
+        self.resume_after_errors = resume;
+        self
+    }
+}
+
+impl Default for FramedConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,22 +1,29 @@
 use crate::{
-    codec::{Decoder, Encoder},
+    codec::{Decoder, EncodeBuf, Encoder},
     error::Error,
 };
 use bytes::{Buf, BytesMut};
-use futures_core::{ready, Stream};
+use futures_core::{Stream, ready};
 use futures_io::{AsyncRead, AsyncWrite};
 use futures_sink::Sink;
 use pin_project_lite::pin_project;
 use std::{
     borrow::{Borrow, BorrowMut},
-    io,
+    io::{self, IoSlice},
+    mem,
     ops::Deref,
     pin::Pin,
     task::{Context, Poll},
 };
 
-const INITIAL_CAPACITY: usize = 8 * 1024;
-const BACKPRESSURE_BOUNDARY: usize = INITIAL_CAPACITY;
+use super::FramedConfig;
+
+pub(super) const INITIAL_CAPACITY: usize = 8 * 1024;
+pub(super) const BACKPRESSURE_BOUNDARY: usize = INITIAL_CAPACITY;
+pub(super) const MAX_READ_SIZE: usize = 1024 * 1024;
+const MIN_READ_SIZE: usize = 1024;
+// Enough slices for a run of header and payload pairs, and well under any `IOV_MAX`.
+const MAX_WRITE_SLICES: usize = 64;
 
 pin_project! {
     #[derive(Debug)]
@@ -47,7 +54,6 @@ where
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let mut pinned = self.project();
         let state: &mut ReadFrame = pinned.state.borrow_mut();
-        let mut buf = [0u8; INITIAL_CAPACITY];
 
         loop {
             // Return `None` if we have encountered an error from the underlying decoder
@@ -63,11 +69,10 @@ where
                 // pausing or framing
                 if state.eof {
                     // pausing
-                    let frame = pinned.codec.decode_eof(&mut state.buffer).map_err(|err| {
-                        log::trace!("Got an error, going to errored state");
-                        state.has_errored = true;
-                        Error::Codec(err)
-                    })?;
+                    let frame = pinned
+                        .codec
+                        .decode_eof(&mut state.buffer)
+                        .map_err(|err| state.codec_error(pinned.codec, err))?;
                     if frame.is_none() {
                         // prepare pausing -> paused
                         state.is_readable = false;
@@ -79,11 +84,11 @@ where
                 // framing
                 log::trace!("Attempting to decode a frame");
 
-                if let Some(frame) = pinned.codec.decode(&mut state.buffer).map_err(|err| {
-                    log::trace!("Got an error, going to errored state");
-                    state.has_errored = true;
-                    Error::Codec(err)
-                })? {
+                if let Some(frame) = pinned
+                    .codec
+                    .decode(&mut state.buffer)
+                    .map_err(|err| state.codec_error(pinned.codec, err))?
+                {
                     log::trace!("Frame decoded from buffer");
                     // implicit framing -> framing
                     return Poll::Ready(Some(Ok(frame)));
@@ -94,12 +99,36 @@ where
             }
 
             // reading or paused
-            let bytes_read = ready!(pinned.inner.as_mut().poll_read(cx, &mut buf).map_err(|err| {
-                log::trace!("Got an error, going to errored state");
-                state.has_errored = true;
-                err
-            }))?;
-            state.buffer.extend_from_slice(&buf[.. bytes_read]);
+            //
+            // Reads go straight into the buffer. Without `unsafe` the spare capacity can't be
+            // handed out uninitialized, so the buffer is grown with zeroes first and trimmed back
+            // to what was actually read afterwards. A pending read keeps its zeroed tail for the
+            // next attempt instead of clearing it again.
+            let len = state.buffer.len();
+            let read_size = state.read_size;
+            state.extend_initialized(read_size);
+            let poll = pinned
+                .inner
+                .as_mut()
+                .poll_read(cx, &mut state.buffer[len ..])
+                .map_ok(|bytes_read| bytes_read.min(read_size));
+            let bytes_read = match poll {
+                Poll::Ready(Ok(bytes_read)) => {
+                    state.buffer.truncate(len + bytes_read);
+                    bytes_read
+                },
+                Poll::Ready(Err(err)) => {
+                    log::trace!("Got an error, going to errored state");
+                    state.buffer.truncate(len);
+                    state.has_errored = true;
+                    return Poll::Ready(Some(Err(err.into())));
+                },
+                Poll::Pending => {
+                    state.tail = state.buffer.split_off(len);
+                    return Poll::Pending;
+                },
+            };
+            state.adapt_read_size(bytes_read, read_size);
 
             if bytes_read == 0 {
                 if state.eof {
@@ -135,7 +164,9 @@ where
         while state.buffer.len() > limit {
             log::trace!("Writing; remaining = {}", state.buffer.len());
 
-            let num_write = ready!(pinned.inner.as_mut().poll_write(cx, &state.buffer))?;
+            let mut slices = [IoSlice::new(&[]); MAX_WRITE_SLICES];
+            let n = state.buffer.chunks_vectored(&mut slices);
+            let num_write = ready!(pinned.inner.as_mut().poll_write_vectored(cx, &slices[.. n]))?;
 
             if num_write == 0 {
                 return Poll::Ready(Err(io::Error::new(
@@ -149,7 +180,9 @@ where
 
         log::trace!("Framed transport flushed");
 
-        if orig_len != state.buffer.len() {
+        // An explicit flush always reaches the underlying writer, even if the buffer was already
+        // drained by `poll_ready`, so that writers which buffer internally push their data out.
+        if limit == 0 || orig_len != state.buffer.len() {
             pinned.inner.poll_flush(cx)
         } else {
             Poll::Ready(Ok(()))
@@ -166,14 +199,15 @@ where
     type Error = Error<U::Error>;
 
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.poll_flush_until(cx, BACKPRESSURE_BOUNDARY - 1).map_err(Into::into)
+        let limit = self.state.borrow().high_water_mark.saturating_sub(1);
+        self.poll_flush_until(cx, limit).map_err(Into::into)
     }
 
     fn start_send(self: Pin<&mut Self>, item: U::Item) -> Result<(), Self::Error> {
         let pinned = self.project();
         pinned
             .codec
-            .encode(item, &mut pinned.state.borrow_mut().buffer)
+            .encode_vectored(item, &mut pinned.state.borrow_mut().buffer)
             .map_err(Error::Codec)
     }
 
@@ -192,24 +226,26 @@ pub(super) struct ReadFrame {
     pub(super) eof: bool,
     pub(super) has_errored: bool,
     pub(super) is_readable: bool,
+    // How many bytes the next read asks for.
+    pub(super) read_size: usize,
+    pub(super) max_read_size: usize,
+    pub(super) resume_after_errors: bool,
+    // Initialized bytes directly following `buffer` in memory, set aside by a pending read so
+    // that decoders and callers never see them. Its length is how much of the next read's space
+    // is already initialized.
+    tail: BytesMut,
 }
 
-impl Default for ReadFrame {
-    fn default() -> Self {
-        Self {
-            buffer: BytesMut::with_capacity(INITIAL_CAPACITY),
-            eof: false,
-            has_errored: false,
-            is_readable: false,
-        }
+impl ReadFrame {
+    pub(super) fn with_config(config: &FramedConfig) -> Self {
+        Self::from_buffer(BytesMut::new(), config)
     }
-}
 
-impl From<BytesMut> for ReadFrame {
-    fn from(mut buffer: BytesMut) -> Self {
+    // Resumes reading with bytes left over from an earlier framed transport.
+    pub(super) fn from_buffer(mut buffer: BytesMut, config: &FramedConfig) -> Self {
         let size = buffer.capacity();
-        if size < INITIAL_CAPACITY {
-            buffer.reserve(INITIAL_CAPACITY - size);
+        if size < config.read_capacity {
+            buffer.reserve(config.read_capacity - size);
         }
 
         Self {
@@ -217,30 +253,75 @@ impl From<BytesMut> for ReadFrame {
             eof: false,
             has_errored: false,
             is_readable: size > 0,
+            read_size: INITIAL_CAPACITY.min(config.max_read_size),
+            max_read_size: config.max_read_size,
+            resume_after_errors: config.resume_after_errors,
+            tail: BytesMut::new(),
         }
     }
-}
 
-pub(super) struct WriteFrame {
-    pub(super) buffer: BytesMut,
+    // Grows the buffer by `additional` initialized bytes for a read to fill. The tail left by a
+    // pending read is rejoined in place, so only capacity beyond it gets zeroed.
+    fn extend_initialized(&mut self, additional: usize) {
+        let len = self.buffer.len();
+        self.buffer.unsplit(mem::take(&mut self.tail));
+        self.buffer.resize(len + additional, 0);
+    }
+
+    fn codec_error<U: Decoder>(&mut self, codec: &U, err: U::Error) -> Error<U::Error> {
+        if self.resume_after_errors && codec.is_recoverable(&err) {
+            log::trace!("Got a recoverable error, staying in the current state");
+        } else {
+            log::trace!("Got an error, going to errored state");
+            self.has_errored = true;
+        }
+        Error::Codec(err)
+    }
+
+    // Doubles the read size after a read that filled the whole request, and halves it after one
+    // that filled less than half, so that zero-filling stays proportional to the data read.
+    fn adapt_read_size(&mut self, bytes_read: usize, requested: usize) {
+        if bytes_read == requested {
+            self.read_size = (self.read_size * 2).min(self.max_read_size);
+        } else if bytes_read < requested / 2 {
+            self.read_size = (self.read_size / 2).max(MIN_READ_SIZE.min(self.max_read_size));
+        }
+    }
 }
 
-impl Default for WriteFrame {
+impl Default for ReadFrame {
     fn default() -> Self {
-        Self {
-            buffer: BytesMut::with_capacity(INITIAL_CAPACITY),
-        }
+        Self::with_config(&FramedConfig::default())
     }
 }
 
-impl From<BytesMut> for WriteFrame {
-    fn from(mut buffer: BytesMut) -> Self {
+pub(super) struct WriteFrame {
+    pub(super) buffer: EncodeBuf,
+    pub(super) high_water_mark: usize,
+}
+
+impl WriteFrame {
+    pub(super) fn with_config(config: &FramedConfig) -> Self {
+        Self::from_buffer(BytesMut::new(), config)
+    }
+
+    // Resumes writing with bytes left over from an earlier framed transport.
+    pub(super) fn from_buffer(mut buffer: BytesMut, config: &FramedConfig) -> Self {
         let size = buffer.capacity();
-        if size < INITIAL_CAPACITY {
-            buffer.reserve(INITIAL_CAPACITY - size);
+        if size < config.write_capacity {
+            buffer.reserve(config.write_capacity - size);
         }
 
-        Self { buffer }
+        Self {
+            buffer: buffer.into(),
+            high_water_mark: config.write_high_water_mark,
+        }
+    }
+}
+
+impl Default for WriteFrame {
+    fn default() -> Self {
+        Self::with_config(&FramedConfig::default())
     }
 }
 
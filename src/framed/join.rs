@@ -0,0 +1,110 @@
+use super::Framed;
+use futures_io::{AsyncRead, AsyncWrite};
+use pin_project_lite::pin_project;
+use std::{
+    io::{self, IoSlice, IoSliceMut},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pin_project! {
+    // Joins a separate reader and writer, such as a child process's stdout and stdin, into one
+    // I/O object.
+    #[derive(Debug)]
+    pub struct Join<R, W> {
+        #[pin]
+        reader: R,
+        #[pin]
+        writer: W,
+    }
+}
+
+impl<R, W> Join<R, W> {
+    #[allow(missing_docs)]
+    pub const fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer }
+    }
+
+    #[allow(missing_docs)]
+    pub fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    #[allow(missing_docs)]
+    pub fn reader_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    #[allow(missing_docs)]
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    #[allow(missing_docs)]
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    #[allow(missing_docs)]
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl<R: AsyncRead, W> AsyncRead for Join<R, W> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.project().reader.poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().reader.poll_read_vectored(cx, bufs)
+    }
+}
+
+impl<R, W: AsyncWrite> AsyncWrite for Join<R, W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.project().writer.poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        self.project().writer.poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().writer.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().writer.poll_close(cx)
+    }
+}
+
+impl<R, W, U> Framed<Join<R, W>, U> {
+    pub fn from_pair(reader: R, writer: W, codec: U) -> Self {
+        Self::new(Join::new(reader, writer), codec)
+    }
+
+    pub fn reader(&self) -> &R {
+        self.get_ref().reader()
+    }
+
+    pub fn reader_mut(&mut self) -> &mut R {
+        self.get_mut().reader_mut()
+    }
+
+    pub fn writer(&self) -> &W {
+        self.get_ref().writer()
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        self.get_mut().writer_mut()
+    }
+}
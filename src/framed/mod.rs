@@ -1,12 +1,21 @@
+mod config;
 mod inner;
+mod join;
 mod read;
+mod split;
 mod write;
 
-pub use self::{read::FramedRead, write::FramedWrite};
+pub use self::{
+    config::FramedConfig,
+    join::Join,
+    read::FramedRead,
+    split::{ReadHalf, ReuniteError, WriteHalf},
+    write::FramedWrite,
+};
 
 use self::inner::{FramedInner, RWFrames, ReadFrame, WriteFrame};
 use crate::{
-    codec::{Decoder, Encoder},
+    codec::{Decoder, EncodeBuf, Encoder, PairCodec},
     error::Error,
 };
 use bytes::BytesMut;
@@ -38,32 +47,38 @@ impl<T, U> Framed<T, U> {
         }
     }
 
+    // Sizes only the read buffer; see `with_config` for the rest.
     pub fn with_capacity(inner: T, codec: U, capacity: usize) -> Framed<T, U> {
+        Self::with_config(inner, codec, FramedConfig::new().read_capacity(capacity))
+    }
+
+    pub fn with_config(inner: T, codec: U, config: FramedConfig) -> Framed<T, U> {
         Framed {
             inner: FramedInner {
                 inner,
                 codec,
                 state: RWFrames {
-                    read: ReadFrame {
-                        buffer: BytesMut::with_capacity(capacity),
-                        eof: false,
-                        has_errored: false,
-                        is_readable: false,
-                    },
-                    write: WriteFrame::default(),
+                    read: ReadFrame::with_config(&config),
+                    write: WriteFrame::with_config(&config),
                 },
             },
         }
     }
 
     pub fn from_parts(parts: FramedParts<T, U>) -> Framed<T, U> {
+        Self::from_parts_with_config(parts, FramedConfig::new())
+    }
+
+    // `FramedParts` doesn't carry the configuration, so a transport taken apart with
+    // `into_parts` needs its settings passed back in here to keep them.
+    pub fn from_parts_with_config(parts: FramedParts<T, U>, config: FramedConfig) -> Framed<T, U> {
         Framed {
             inner: FramedInner {
                 inner: parts.io,
                 codec: parts.codec,
                 state: RWFrames {
-                    read: parts.read_buf.into(),
-                    write: parts.write_buf.into(),
+                    read: ReadFrame::from_buffer(parts.read_buf, &config),
+                    write: WriteFrame::from_buffer(parts.write_buf, &config),
                 },
             },
         }
@@ -97,21 +112,67 @@ impl<T, U> Framed<T, U> {
         &mut self.inner.state.read.buffer
     }
 
+    pub fn write_buffer(&self) -> &EncodeBuf {
+        &self.inner.state.write.buffer
+    }
+
+    pub fn write_buffer_mut(&mut self) -> &mut EncodeBuf {
+        &mut self.inner.state.write.buffer
+    }
+
+    // Any buffered bytes are dropped; use `into_parts` to keep them.
     pub fn into_inner(self) -> T {
         self.inner.inner
     }
 
+    // Swaps the codec, keeping buffered bytes and stream state, for protocols that change
+    // framing partway through a connection.
+    pub fn map_codec<C, F>(self, map: F) -> Framed<T, C>
+    where
+        F: FnOnce(U) -> C,
+    {
+        Framed {
+            inner: FramedInner {
+                inner: self.inner.inner,
+                codec: map(self.inner.codec),
+                state: self.inner.state,
+            },
+        }
+    }
+
     pub fn into_parts(self) -> FramedParts<T, U> {
         FramedParts {
             io: self.inner.inner,
             codec: self.inner.codec,
             read_buf: self.inner.state.read.buffer,
-            write_buf: self.inner.state.write.buffer,
+            write_buf: self.inner.state.write.buffer.into(),
             _priv: (),
         }
     }
 }
 
+impl<T, D, E> Framed<T, PairCodec<D, E>> {
+    pub fn with_codecs(inner: T, decoder: D, encoder: E) -> Self {
+        Self::new(inner, PairCodec::new(decoder, encoder))
+    }
+
+    pub fn decoder(&self) -> &D {
+        self.codec().decoder()
+    }
+
+    pub fn decoder_mut(&mut self) -> &mut D {
+        self.codec_mut().decoder_mut()
+    }
+
+    pub fn encoder(&self) -> &E {
+        self.codec().encoder()
+    }
+
+    pub fn encoder_mut(&mut self) -> &mut E {
+        self.codec_mut().encoder_mut()
+    }
+}
+
 impl<T, U> Stream for Framed<T, U>
 where
     T: AsyncRead,
@@ -1,4 +1,8 @@
-use super::inner::{FramedInner, ReadFrame};
+use super::{
+    FramedConfig,
+    FramedParts,
+    inner::{FramedInner, ReadFrame},
+};
 use crate::{codec::Decoder, error::Error};
 use bytes::BytesMut;
 use futures_core::Stream;
@@ -14,7 +18,9 @@ use std::{
 pin_project! {
     pub struct FramedRead<T, D> {
         #[pin]
-        inner: FramedInner<T, D, ReadFrame>,
+        pub(super) inner: FramedInner<T, D, ReadFrame>,
+        // The write buffer of the parts this was built from, kept only to hand back in `into_parts`.
+        pub(super) write_buf: BytesMut,
     }
 }
 
@@ -30,26 +36,45 @@ where
                 codec: decoder,
                 state: Default::default(),
             },
+            write_buf: BytesMut::new(),
         }
     }
 
     pub fn with_capacity(inner: T, decoder: D, capacity: usize) -> FramedRead<T, D> {
+        Self::with_config(inner, decoder, FramedConfig::new().read_capacity(capacity))
+    }
+
+    // Only the read capacity, maximum read size and error recovery apply.
+    pub fn with_config(inner: T, decoder: D, config: FramedConfig) -> FramedRead<T, D> {
         FramedRead {
             inner: FramedInner {
                 inner,
                 codec: decoder,
-                state: ReadFrame {
-                    buffer: BytesMut::with_capacity(capacity),
-                    eof: false,
-                    has_errored: false,
-                    is_readable: false,
-                },
+                state: ReadFrame::with_config(&config),
             },
+            write_buf: BytesMut::new(),
         }
     }
 }
 
 impl<T, D> FramedRead<T, D> {
+    // A `FramedRead` has no use for the write buffer of `parts`; it is kept untouched and handed
+    // back by `into_parts`.
+    pub fn from_parts(parts: FramedParts<T, D>) -> FramedRead<T, D> {
+        Self::from_parts_with_config(parts, FramedConfig::new())
+    }
+
+    pub fn from_parts_with_config(parts: FramedParts<T, D>, config: FramedConfig) -> FramedRead<T, D> {
+        FramedRead {
+            inner: FramedInner {
+                inner: parts.io,
+                codec: parts.codec,
+                state: ReadFrame::from_buffer(parts.read_buf, &config),
+            },
+            write_buf: parts.write_buf,
+        }
+    }
+
     pub fn get_ref(&self) -> &T {
         &self.inner.inner
     }
@@ -66,6 +91,30 @@ impl<T, D> FramedRead<T, D> {
         self.inner.inner
     }
 
+    pub fn map_decoder<C, F>(self, map: F) -> FramedRead<T, C>
+    where
+        F: FnOnce(D) -> C,
+    {
+        FramedRead {
+            inner: FramedInner {
+                inner: self.inner.inner,
+                codec: map(self.inner.codec),
+                state: self.inner.state,
+            },
+            write_buf: self.write_buf,
+        }
+    }
+
+    pub fn into_parts(self) -> FramedParts<T, D> {
+        FramedParts {
+            io: self.inner.inner,
+            codec: self.inner.codec,
+            read_buf: self.inner.state.buffer,
+            write_buf: self.write_buf,
+            _priv: (),
+        }
+    }
+
     pub fn decoder(&self) -> &D {
         &self.inner.codec
     }
@@ -0,0 +1,155 @@
+use super::{
+    Framed,
+    FramedRead,
+    FramedWrite,
+    inner::{FramedInner, RWFrames},
+};
+use bytes::BytesMut;
+use futures_io::{AsyncRead, AsyncWrite};
+use std::{
+    fmt,
+    io::{self, IoSlice, IoSliceMut},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+// The halves of an I/O object split by `Framed::split`. They share it through an `Arc` and poll
+// it by shared reference, the way sockets are read and written from several tasks at once, so
+// neither half takes a lock or waits on the other.
+pub struct ReadHalf<T> {
+    io: Arc<T>,
+}
+
+pub struct WriteHalf<T> {
+    io: Arc<T>,
+}
+
+impl<T> AsyncRead for ReadHalf<T>
+where
+    for<'a> &'a T: AsyncRead,
+{
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self.io).poll_read(cx, buf)
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self.io).poll_read_vectored(cx, bufs)
+    }
+}
+
+impl<T> AsyncWrite for WriteHalf<T>
+where
+    for<'a> &'a T: AsyncWrite,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self.io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self.io).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self.io).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self.io).poll_close(cx)
+    }
+}
+
+impl<T> fmt::Debug for ReadHalf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadHalf").finish()
+    }
+}
+
+impl<T> fmt::Debug for WriteHalf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteHalf").finish()
+    }
+}
+
+// Returned by `FramedRead::reunite` when the halves came from different `Framed`s.
+#[derive(Debug, thiserror::Error)]
+#[error("tried to reunite halves that are not from the same Framed")]
+pub struct ReuniteError<T, U>(pub FramedRead<ReadHalf<T>, U>, pub FramedWrite<WriteHalf<T>, U>);
+
+impl<T, U> Framed<T, U> {
+    // Splits into a stream and a sink that can be driven from separate tasks. Each half keeps its
+    // own buffer and a copy of the codec.
+    //
+    // Only I/O objects that can be read and written through a shared reference can be split this
+    // way, such as the socket types of async-std and smol, which implement the I/O traits for
+    // `&T`. Most other transports, TLS streams and compression wrappers among them, only
+    // implement them for `T`; split those with `futures::io::AsyncReadExt::split` instead and
+    // frame each half with its own `FramedRead` or `FramedWrite`.
+    pub fn split(self) -> (FramedRead<ReadHalf<T>, U>, FramedWrite<WriteHalf<T>, U>)
+    where
+        for<'a> &'a T: AsyncRead + AsyncWrite,
+        U: Clone,
+    {
+        let FramedInner { inner, codec, state } = self.inner;
+        let io = Arc::new(inner);
+        let read = FramedRead {
+            inner: FramedInner {
+                inner: ReadHalf { io: io.clone() },
+                codec: codec.clone(),
+                state: state.read,
+            },
+            write_buf: BytesMut::new(),
+        };
+        let write = FramedWrite {
+            inner: FramedInner {
+                inner: WriteHalf { io },
+                codec,
+                state: state.write,
+            },
+            read_buf: BytesMut::new(),
+        };
+        (read, write)
+    }
+}
+
+impl<T, U> FramedRead<ReadHalf<T>, U> {
+    // The reunited `Framed` carries on with the read half's codec. The write half's codec is
+    // handed back next to it rather than dropped, since it may hold encoder state the read half's
+    // copy lacks; a codec whose directions both keep state can be put back together with
+    // `map_codec`, for instance into a `PairCodec` of the two. The error hands both halves back
+    // unchanged.
+    #[allow(clippy::result_large_err)]
+    pub fn reunite(self, other: FramedWrite<WriteHalf<T>, U>) -> Result<(Framed<T, U>, U), ReuniteError<T, U>> {
+        if !Arc::ptr_eq(&self.inner.inner.io, &other.inner.inner.io) {
+            return Err(ReuniteError(self, other));
+        }
+        let FramedInner {
+            inner,
+            codec,
+            state: read,
+        } = self.inner;
+        let FramedInner {
+            inner: write_half,
+            codec: write_codec,
+            state: write,
+        } = other.inner;
+        drop(write_half);
+        let io = Arc::try_unwrap(inner.io).ok().expect("both halves are owned here");
+        let framed = Framed {
+            inner: FramedInner {
+                inner: io,
+                codec,
+                state: RWFrames { read, write },
+            },
+        };
+        Ok((framed, write_codec))
+    }
+}
@@ -1,5 +1,13 @@
-use super::inner::{FramedInner, WriteFrame};
-use crate::{codec::Encoder, error::Error};
+use super::{
+    FramedConfig,
+    FramedParts,
+    inner::{FramedInner, WriteFrame},
+};
+use crate::{
+    codec::{EncodeBuf, Encoder},
+    error::Error,
+};
+use bytes::BytesMut;
 use futures_core::Stream;
 use futures_io::AsyncWrite;
 use futures_sink::Sink;
@@ -13,7 +21,9 @@ use std::{
 pin_project! {
     pub struct FramedWrite<T, E> {
         #[pin]
-        inner: FramedInner<T, E, WriteFrame>,
+        pub(super) inner: FramedInner<T, E, WriteFrame>,
+        // The read buffer of the parts this was built from, kept only to hand back in `into_parts`.
+        pub(super) read_buf: BytesMut,
     }
 }
 
@@ -28,11 +38,45 @@ where
                 codec: encoder,
                 state: WriteFrame::default(),
             },
+            read_buf: BytesMut::new(),
+        }
+    }
+
+    pub fn with_capacity(inner: T, encoder: E, capacity: usize) -> FramedWrite<T, E> {
+        Self::with_config(inner, encoder, FramedConfig::new().write_capacity(capacity))
+    }
+
+    // Only the write capacity and high-water mark apply.
+    pub fn with_config(inner: T, encoder: E, config: FramedConfig) -> FramedWrite<T, E> {
+        FramedWrite {
+            inner: FramedInner {
+                inner,
+                codec: encoder,
+                state: WriteFrame::with_config(&config),
+            },
+            read_buf: BytesMut::new(),
         }
     }
 }
 
 impl<T, E> FramedWrite<T, E> {
+    // A `FramedWrite` has no use for the read buffer of `parts`; it is kept untouched and handed
+    // back by `into_parts`.
+    pub fn from_parts(parts: FramedParts<T, E>) -> FramedWrite<T, E> {
+        Self::from_parts_with_config(parts, FramedConfig::new())
+    }
+
+    pub fn from_parts_with_config(parts: FramedParts<T, E>, config: FramedConfig) -> FramedWrite<T, E> {
+        FramedWrite {
+            inner: FramedInner {
+                inner: parts.io,
+                codec: parts.codec,
+                state: WriteFrame::from_buffer(parts.write_buf, &config),
+            },
+            read_buf: parts.read_buf,
+        }
+    }
+
     pub fn get_ref(&self) -> &T {
         &self.inner.inner
     }
@@ -45,10 +89,35 @@ impl<T, E> FramedWrite<T, E> {
         self.project().inner.project().inner
     }
 
+    // Any frames not yet flushed are dropped; use `into_parts` to keep them.
     pub fn into_inner(self) -> T {
         self.inner.inner
     }
 
+    pub fn map_encoder<C, F>(self, map: F) -> FramedWrite<T, C>
+    where
+        F: FnOnce(E) -> C,
+    {
+        FramedWrite {
+            inner: FramedInner {
+                inner: self.inner.inner,
+                codec: map(self.inner.codec),
+                state: self.inner.state,
+            },
+            read_buf: self.read_buf,
+        }
+    }
+
+    pub fn into_parts(self) -> FramedParts<T, E> {
+        FramedParts {
+            io: self.inner.inner,
+            codec: self.inner.codec,
+            read_buf: self.read_buf,
+            write_buf: self.inner.state.buffer.into(),
+            _priv: (),
+        }
+    }
+
     pub fn encoder(&self) -> &E {
         &self.inner.codec
     }
@@ -56,6 +125,14 @@ impl<T, E> FramedWrite<T, E> {
     pub fn encoder_mut(&mut self) -> &mut E {
         &mut self.inner.codec
     }
+
+    pub fn write_buffer(&self) -> &EncodeBuf {
+        &self.inner.state.buffer
+    }
+
+    pub fn write_buffer_mut(&mut self) -> &mut EncodeBuf {
+        &mut self.inner.state.buffer
+    }
 }
 
 impl<T, E> Sink<E::Item> for FramedWrite<T, E>
@@ -102,7 +179,7 @@ where
         f.debug_struct("FramedWrite")
             .field("inner", &self.get_ref())
             .field("encoder", &self.encoder())
-            .field("buffer", &self.inner.state.buffer)
+            .field("buffer", &self.write_buffer())
             .finish()
     }
 }
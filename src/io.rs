@@ -0,0 +1,341 @@
+use futures_core::ready;
+use futures_io::{AsyncRead, AsyncWrite};
+use pin_project_lite::pin_project;
+use std::{
+    io::{self, BufRead, Read, Write},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+// A synchronous compressor that writes its output into a `Vec<u8>`.
+#[allow(missing_docs)]
+pub trait StreamCoder: Write {
+    fn output(&mut self) -> &mut Vec<u8>;
+
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+#[cfg(feature = "gzip")]
+impl StreamCoder for flate2::write::GzEncoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.try_finish()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl StreamCoder for zstd::stream::write::Encoder<'static, Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.do_finish()
+    }
+}
+
+// A synchronous decompressor reading from an `InputBuf`. It writes straight into the caller's
+// buffer, so a small input can't expand into an unbounded amount of memory.
+#[allow(missing_docs)]
+pub trait StreamDecoder: Read {
+    fn input(&mut self) -> &mut InputBuf;
+}
+
+#[cfg(feature = "gzip")]
+impl StreamDecoder for flate2::bufread::GzDecoder<InputBuf> {
+    fn input(&mut self) -> &mut InputBuf {
+        self.get_mut()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl StreamDecoder for zstd::stream::read::Decoder<'static, InputBuf> {
+    fn input(&mut self) -> &mut InputBuf {
+        self.get_mut()
+    }
+}
+
+// Compressed input waiting to be decoded. Once drained it fails with `WouldBlock` until more
+// is read from the underlying reader, which the decoders above survive without losing their
+// place.
+#[derive(Debug)]
+pub struct InputBuf {
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+    started: bool,
+}
+
+impl InputBuf {
+    #[allow(missing_docs)]
+    pub fn new() -> Self {
+        Self {
+            buf: vec![0; CHUNK_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            eof: false,
+            started: false,
+        }
+    }
+
+    fn poll_fill<R: AsyncRead>(&mut self, reader: Pin<&mut R>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let bytes_read = ready!(reader.poll_read(cx, &mut self.buf))?;
+        self.pos = 0;
+        self.len = bytes_read;
+        self.eof = bytes_read == 0;
+        self.started |= bytes_read > 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Default for InputBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Read for InputBuf {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[.. len].copy_from_slice(&available[.. len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for InputBuf {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.len && !self.eof {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        Ok(&self.buf[self.pos .. self.len])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.len);
+    }
+}
+
+pin_project! {
+    // Compresses everything written to it before passing it on to `inner`. Flushing performs a
+    // sync flush of the compressor, so `Sink::poll_flush` on a `FramedWrite` delivers frames.
+    #[derive(Debug)]
+    pub struct CompressWriter<W, C> {
+        #[pin]
+        inner: W,
+        coder: C,
+        written: usize,
+        needs_flush: bool,
+        finished: bool,
+    }
+}
+
+#[cfg(feature = "gzip")]
+pub type GzipWriter<W> = CompressWriter<W, flate2::write::GzEncoder<Vec<u8>>>;
+
+#[cfg(feature = "zstd")]
+pub type ZstdWriter<W> = CompressWriter<W, zstd::stream::write::Encoder<'static, Vec<u8>>>;
+
+impl<W, C> CompressWriter<W, C> {
+    #[allow(missing_docs)]
+    pub fn with_coder(inner: W, coder: C) -> Self {
+        Self {
+            inner,
+            coder,
+            written: 0,
+            needs_flush: false,
+            finished: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut W> {
+        self.project().inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<W> CompressWriter<W, flate2::write::GzEncoder<Vec<u8>>> {
+    #[allow(missing_docs)]
+    pub fn gzip(inner: W) -> Self {
+        Self::with_coder(
+            inner,
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()),
+        )
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W> CompressWriter<W, zstd::stream::write::Encoder<'static, Vec<u8>>> {
+    #[allow(missing_docs)]
+    pub fn zstd(inner: W) -> io::Result<Self> {
+        let coder = zstd::stream::write::Encoder::new(Vec::new(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+        Ok(Self::with_coder(inner, coder))
+    }
+}
+
+impl<W, C> CompressWriter<W, C>
+where
+    W: AsyncWrite,
+    C: StreamCoder,
+{
+    // Writes out compressed bytes until at most `limit` remain buffered.
+    fn poll_drain(self: Pin<&mut Self>, cx: &mut Context<'_>, limit: usize) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        let output = this.coder.output();
+
+        while output.len() - *this.written > limit {
+            let num_write = ready!(this.inner.as_mut().poll_write(cx, &output[*this.written ..]))?;
+            if num_write == 0 {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "CompressWriter: end of output",
+                )));
+            }
+            *this.written += num_write;
+        }
+
+        // Drop what has been written, so that a sink taking partial writes can't make the
+        // buffer grow until the next flush.
+        output.drain(.. *this.written);
+        *this.written = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W, C> AsyncWrite for CompressWriter<W, C>
+where
+    W: AsyncWrite,
+    C: StreamCoder,
+{
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if self.finished {
+            return Poll::Ready(Err(io::Error::other("CompressWriter: write after close")));
+        }
+        ready!(self.as_mut().poll_drain(cx, CHUNK_SIZE - 1))?;
+        let this = self.project();
+        let num_write = this.coder.write(buf)?;
+        *this.needs_flush = true;
+        Poll::Ready(Ok(num_write))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.as_mut().project();
+        if *this.needs_flush {
+            this.coder.flush()?;
+            *this.needs_flush = false;
+        }
+        ready!(self.as_mut().poll_drain(cx, 0))?;
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.as_mut().project();
+        if !*this.finished {
+            this.coder.finish()?;
+            *this.finished = true;
+            *this.needs_flush = false;
+        }
+        ready!(self.as_mut().poll_drain(cx, 0))?;
+        self.project().inner.poll_close(cx)
+    }
+}
+
+pin_project! {
+    // Decompresses everything read from `inner`. A stream that ends partway through fails with
+    // `UnexpectedEof`, while one that ends before any input is a clean end of file.
+    #[derive(Debug)]
+    pub struct DecompressReader<R, C> {
+        #[pin]
+        inner: R,
+        coder: C,
+    }
+}
+
+#[cfg(feature = "gzip")]
+pub type GzipReader<R> = DecompressReader<R, flate2::bufread::GzDecoder<InputBuf>>;
+
+#[cfg(feature = "zstd")]
+pub type ZstdReader<R> = DecompressReader<R, zstd::stream::read::Decoder<'static, InputBuf>>;
+
+impl<R, C> DecompressReader<R, C> {
+    #[allow(missing_docs)]
+    pub fn with_coder(inner: R, coder: C) -> Self {
+        Self { inner, coder }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut R> {
+        self.project().inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<R> DecompressReader<R, flate2::bufread::GzDecoder<InputBuf>> {
+    #[allow(missing_docs)]
+    pub fn gzip(inner: R) -> Self {
+        Self::with_coder(inner, flate2::bufread::GzDecoder::new(InputBuf::new()))
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<R> DecompressReader<R, zstd::stream::read::Decoder<'static, InputBuf>> {
+    #[allow(missing_docs)]
+    pub fn zstd(inner: R) -> io::Result<Self> {
+        Ok(Self::with_coder(
+            inner,
+            zstd::stream::read::Decoder::with_buffer(InputBuf::new())?,
+        ))
+    }
+}
+
+impl<R, C> AsyncRead for DecompressReader<R, C>
+where
+    R: AsyncRead,
+    C: StreamDecoder,
+{
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+
+        loop {
+            match this.coder.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {},
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && !this.coder.input().started => {
+                    return Poll::Ready(Ok(0));
+                },
+                res => return Poll::Ready(res),
+            }
+            ready!(this.coder.input().poll_fill(this.inner.as_mut(), cx))?;
+        }
+    }
+}
@@ -5,9 +5,15 @@
 mod codec;
 mod error;
 mod framed;
+mod io;
 
+#[cfg(feature = "gzip")]
+pub use self::io::{GzipReader, GzipWriter};
+#[cfg(feature = "zstd")]
+pub use self::io::{ZstdReader, ZstdWriter};
 pub use self::{
     codec::*,
-    framed::{Framed, FramedParts, FramedRead, FramedWrite},
+    framed::{Framed, FramedConfig, FramedParts, FramedRead, FramedWrite, Join, ReadHalf, ReuniteError, WriteHalf},
+    io::{CompressWriter, DecompressReader, InputBuf, StreamCoder, StreamDecoder},
 };
 pub use bytes::{Bytes, BytesMut};
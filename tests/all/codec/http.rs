@@ -0,0 +1,22 @@
+use async_codec_lite::{Chunk, ChunkedCodec, Framed, FramedParts, HttpHead, RequestHeadCodec};
+use futures_lite::future::block_on;
+use futures_util::{io::Cursor, stream::StreamExt};
+
+#[test]
+fn head_codec_hands_off_to_body_codec() {
+    let request = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+    let mut framed = Framed::new(Cursor::new(request.to_vec()), RequestHeadCodec::new());
+
+    let head = block_on(framed.next()).unwrap().unwrap();
+    assert_eq!(head.target, "/upload");
+    assert_eq!(
+        head.header("transfer-encoding").map(|value| &value[..]),
+        Some(&b"chunked"[..])
+    );
+
+    let parts = framed.into_parts();
+    let mut body = FramedParts::new::<Chunk>(parts.io, ChunkedCodec::new());
+    body.read_buf = parts.read_buf;
+    let chunks = block_on(Framed::from_parts(body).map(|res| res.unwrap()).collect::<Vec<_>>());
+    assert_eq!(chunks, vec![Chunk::Data("hello".into()), Chunk::End(vec![])]);
+}
@@ -1,4 +1,5 @@
 mod bytes;
+mod http;
 mod length;
 #[cfg(feature = "lines")]
 mod lines;
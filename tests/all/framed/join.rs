@@ -0,0 +1,34 @@
+use async_codec_lite::{Bytes, Framed, Join, LengthCodec};
+use futures_lite::future::block_on;
+use futures_util::{io::Cursor, sink::SinkExt, stream::StreamExt};
+
+#[test]
+fn frames_over_separate_reader_and_writer() {
+    let reader = Cursor::new(b"\x04ping".to_vec());
+    let writer = Cursor::new(Vec::new());
+    let mut framed = Framed::from_pair(reader, writer, LengthCodec::<u8>::new());
+
+    let ping = block_on(framed.next()).unwrap().unwrap();
+    assert_eq!(ping, Bytes::from_static(b"ping"));
+    block_on(framed.send(Bytes::from_static(b"pong"))).unwrap();
+
+    assert_eq!(framed.reader().position(), 5);
+    assert_eq!(framed.writer().get_ref(), b"\x04pong");
+    let (_, writer) = framed.into_inner().into_inner();
+    assert_eq!(writer.into_inner(), b"\x04pong");
+}
+
+#[test]
+fn frames_with_separate_decoder_and_encoder() {
+    use async_codec_lite::BytesCodec;
+
+    let reader = Cursor::new(b"\x03abc".to_vec());
+    let writer = Cursor::new(Vec::new());
+    let mut framed = Framed::with_codecs(Join::new(reader, writer), LengthCodec::<u8>::new(), BytesCodec);
+    let abc = block_on(framed.next()).unwrap().unwrap();
+    assert_eq!(abc, Bytes::from_static(b"abc"));
+    block_on(framed.send(Bytes::from_static(b"raw"))).unwrap();
+    assert_eq!(framed.writer().get_ref(), b"raw");
+    let _: &mut BytesCodec = framed.encoder_mut();
+    let _: &mut LengthCodec<u8> = framed.decoder_mut();
+}
@@ -1,2 +1,4 @@
+mod join;
 mod read;
+mod split;
 mod write;
@@ -81,3 +81,228 @@ fn read_few_messages() {
         assert_eq!(item, 'a');
     }
 }
+
+struct RecordReadSizes {
+    remaining: usize,
+    short_reads: usize,
+    requested: Vec<usize>,
+}
+impl AsyncRead for RecordReadSizes {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.requested.push(buf.len());
+        let len = if self.short_reads > 0 {
+            self.short_reads -= 1;
+            1
+        } else {
+            buf.len().min(self.remaining)
+        };
+        buf[.. len].fill(b'b');
+        self.remaining -= len;
+        Poll::Ready(Ok(len))
+    }
+}
+
+#[test]
+fn read_size_adapts_to_reads() {
+    let input = RecordReadSizes {
+        remaining: 1024 * 1024,
+        short_reads: 0,
+        requested: Vec::new(),
+    };
+    let mut framed = Framed::new(input, AllTheAs);
+    assert!(block_on(framed.next()).is_none());
+    let requested = &framed.get_ref().requested;
+    assert_eq!(requested[.. 3], [8 * 1024, 16 * 1024, 32 * 1024]);
+    assert!(requested.len() < 16);
+
+    let input = RecordReadSizes {
+        remaining: 8,
+        short_reads: 8,
+        requested: Vec::new(),
+    };
+    let mut framed = Framed::new(input, AllTheAs);
+    assert!(block_on(framed.next()).is_none());
+    assert_eq!(framed.get_ref().requested[.. 5], [
+        8 * 1024,
+        4 * 1024,
+        2 * 1024,
+        1024,
+        1024
+    ]);
+}
+
+// Scribbles over the space it is offered while pending, then reports what it saw next time.
+struct PendingScribbler {
+    polls: usize,
+    seen: Vec<u8>,
+}
+impl AsyncRead for PendingScribbler {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.polls += 1;
+        self.seen.push(buf[0]);
+        if self.polls < 3 {
+            buf[0] = 0xaa;
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(0))
+        }
+    }
+}
+
+#[test]
+fn pending_reads_keep_their_initialized_space() {
+    use futures_util::task::noop_waker_ref;
+
+    let io = PendingScribbler {
+        polls: 0,
+        seen: Vec::new(),
+    };
+    let mut framed = Framed::new(io, AllTheAs);
+    let mut cx = Context::from_waker(noop_waker_ref());
+    assert!(framed.poll_next_unpin(&mut cx).is_pending());
+    assert!(framed.read_buffer().is_empty());
+    assert!(framed.poll_next_unpin(&mut cx).is_pending());
+    assert!(block_on(framed.next()).is_none());
+    assert_eq!(framed.get_ref().seen, [0, 0xaa, 0xaa]);
+}
+
+#[test]
+fn parts_keep_buffered_frames() {
+    use async_codec_lite::{Bytes, FramedRead, LengthCodec};
+    use futures_util::io::Cursor;
+
+    let io = Cursor::new(b"\x01a\x02bc".to_vec());
+    let mut framed = FramedRead::new(io, LengthCodec::<u8>::new());
+    let a = block_on(framed.next()).unwrap().unwrap();
+    assert_eq!(a, Bytes::from_static(b"a"));
+
+    let parts = framed.into_parts();
+    assert_eq!(&parts.read_buf[..], b"\x02bc");
+    let mut framed = FramedRead::from_parts(parts);
+    let bc = block_on(framed.next()).unwrap().unwrap();
+    assert_eq!(bc, Bytes::from_static(b"bc"));
+}
+
+#[test]
+fn parts_keep_the_unused_buffer() {
+    use async_codec_lite::{FramedParts, FramedRead, FramedWrite, LengthCodec};
+    use futures_util::io::Cursor;
+
+    let mut parts = FramedParts::new::<u8>(Cursor::new(Vec::<u8>::new()), LengthCodec::<u8>::new());
+    parts.write_buf.extend_from_slice(b"\x01a");
+    let parts = FramedRead::from_parts(parts).into_parts();
+    assert_eq!(&parts.write_buf[..], b"\x01a");
+
+    let mut parts = FramedParts::new::<u8>(Cursor::new(Vec::<u8>::new()), LengthCodec::<u8>::new());
+    parts.read_buf.extend_from_slice(b"\x01b");
+    let parts = FramedWrite::from_parts(parts).into_parts();
+    assert_eq!(&parts.read_buf[..], b"\x01b");
+}
+
+#[test]
+fn parts_can_keep_the_config() {
+    use async_codec_lite::FramedConfig;
+
+    let config = FramedConfig::new().max_read_size(2048);
+    let input = RecordReadSizes {
+        remaining: 64 * 1024,
+        short_reads: 0,
+        requested: Vec::new(),
+    };
+    let framed = Framed::with_config(input, AllTheAs, config);
+    let mut framed = Framed::from_parts_with_config(framed.into_parts(), config);
+    assert!(block_on(framed.next()).is_none());
+    assert!(framed.get_ref().requested.iter().all(|&size| size <= 2048));
+}
+
+#[test]
+fn map_codec_keeps_buffered_bytes() {
+    use async_codec_lite::{Bytes, BytesCodec, LengthCodec};
+    use futures_util::io::Cursor;
+
+    let io = Cursor::new(b"\x05hellorest of stream".to_vec());
+    let mut framed = Framed::new(io, LengthCodec::<u8>::new());
+    let hello = block_on(framed.next()).unwrap().unwrap();
+    assert_eq!(hello, Bytes::from_static(b"hello"));
+
+    let mut framed = framed.map_codec(|_| BytesCodec {});
+    let rest = block_on(framed.next()).unwrap().unwrap();
+    assert_eq!(rest, Bytes::from_static(b"rest of stream"));
+    assert!(block_on(framed.next()).is_none());
+}
+
+struct RejectTheXs;
+
+impl Decoder for RejectTheXs {
+    type Error = io::Error;
+    type Item = u8;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+        match src.split_to(1)[0] {
+            b'x' => Err(io::Error::new(io::ErrorKind::InvalidData, "x")),
+            b => Ok(Some(b)),
+        }
+    }
+
+    fn is_recoverable(&self, err: &Self::Error) -> bool {
+        err.kind() == io::ErrorKind::InvalidData
+    }
+}
+
+#[test]
+fn resumes_after_recoverable_errors() {
+    use async_codec_lite::FramedConfig;
+    use futures_util::io::Cursor;
+
+    let mut framed = Framed::new(Cursor::new(b"axb".to_vec()), RejectTheXs);
+    assert_eq!(block_on(framed.next()).unwrap().unwrap(), b'a');
+    assert!(block_on(framed.next()).unwrap().is_err());
+    assert!(block_on(framed.next()).is_none());
+
+    let config = FramedConfig::new().resume_after_errors(true);
+    let mut framed = Framed::with_config(Cursor::new(b"axb".to_vec()), RejectTheXs, config);
+    assert_eq!(block_on(framed.next()).unwrap().unwrap(), b'a');
+    assert!(block_on(framed.next()).unwrap().is_err());
+    assert_eq!(block_on(framed.next()).unwrap().unwrap(), b'b');
+    assert!(block_on(framed.next()).is_none());
+}
+
+#[cfg(feature = "hdlc")]
+#[test]
+fn hdlc_discards_frames_with_bad_fcs() {
+    use async_codec_lite::{Bytes, Encoder, Fcs, FramedConfig, HdlcCodec};
+    use futures_util::io::Cursor;
+
+    let mut codec = HdlcCodec::new(Fcs::Crc16);
+    let mut wire = BytesMut::new();
+    for payload in [&b"one"[..], b"two", b"three"] {
+        codec.encode(Bytes::from_static(payload), &mut wire).unwrap();
+    }
+    // Corrupt the payload of the second frame.
+    let second = wire.iter().skip(1).position(|&b| b == b't').unwrap() + 1;
+    wire[second] = b'T';
+
+    let config = FramedConfig::new().resume_after_errors(true);
+    let mut framed = Framed::with_config(Cursor::new(wire.to_vec()), codec, config);
+    assert_eq!(block_on(framed.next()).unwrap().unwrap(), Bytes::from_static(b"one"));
+    assert!(block_on(framed.next()).unwrap().is_err());
+    assert_eq!(block_on(framed.next()).unwrap().unwrap(), Bytes::from_static(b"three"));
+    assert!(block_on(framed.next()).is_none());
+}
+
+#[test]
+fn netstrings_resume_after_missing_comma() {
+    use async_codec_lite::{Bytes, FramedConfig, LimitCodec, NetstringCodec};
+    use futures_util::io::Cursor;
+
+    let codec = LimitCodec::new(NetstringCodec::new(), 64);
+    let config = FramedConfig::new().resume_after_errors(true);
+    let mut framed = Framed::with_config(Cursor::new(b"3:one,3:bad;3:two,".to_vec()), codec, config);
+    assert_eq!(block_on(framed.next()).unwrap().unwrap(), Bytes::from_static(b"one"));
+    assert!(block_on(framed.next()).unwrap().is_err());
+    assert_eq!(block_on(framed.next()).unwrap().unwrap(), Bytes::from_static(b"two"));
+    assert!(block_on(framed.next()).is_none());
+}
@@ -0,0 +1,109 @@
+use async_codec_lite::{Bytes, Framed, LengthCodec, PairCodec};
+use futures_lite::future::block_on;
+use futures_util::{
+    io::{AsyncRead, AsyncWrite},
+    sink::SinkExt,
+    stream::StreamExt,
+};
+use std::{
+    cell::RefCell,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+// Reads and writes through a shared reference as well as an owned one, as sockets do.
+#[derive(Debug, Default)]
+struct Pipe {
+    input: RefCell<Vec<u8>>,
+    output: RefCell<Vec<u8>>,
+}
+
+impl Pipe {
+    fn new(input: &[u8]) -> Self {
+        Self {
+            input: RefCell::new(input.to_vec()),
+            output: RefCell::default(),
+        }
+    }
+}
+
+impl AsyncRead for &Pipe {
+    fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut input = self.input.borrow_mut();
+        let len = buf.len().min(input.len());
+        buf[.. len].copy_from_slice(&input[.. len]);
+        input.drain(.. len);
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl AsyncWrite for &Pipe {
+    fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.output.borrow_mut().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for Pipe {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Pipe {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut &*self).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut &*self).poll_close(cx)
+    }
+}
+
+#[test]
+fn split_and_reunite() {
+    let framed = Framed::new(Pipe::new(b"\x02hi\x03abc"), LengthCodec::<u8>::new());
+    let (mut read, mut write) = framed.split();
+
+    let hi = block_on(read.next()).unwrap().unwrap();
+    assert_eq!(hi, Bytes::from_static(b"hi"));
+    block_on(write.send(Bytes::from_static(b"ok"))).unwrap();
+
+    let (mut framed, _) = read.reunite(write).unwrap();
+    let abc = block_on(framed.next()).unwrap().unwrap();
+    assert_eq!(abc, Bytes::from_static(b"abc"));
+    assert_eq!(framed.into_inner().output.into_inner(), b"\x02ok");
+}
+
+#[test]
+fn reunited_codecs_can_be_merged() {
+    let framed = Framed::new(Pipe::new(b"\x01a"), LengthCodec::<u8>::new());
+    let (read, write) = framed.split();
+    let (framed, encoder) = read.reunite(write).unwrap();
+
+    // Keep decoding with the read half's codec and encoding with the write half's.
+    let mut framed = framed.map_codec(|decoder| PairCodec::new(decoder, encoder));
+    assert_eq!(block_on(framed.next()).unwrap().unwrap(), Bytes::from_static(b"a"));
+    block_on(framed.send(Bytes::from_static(b"b"))).unwrap();
+    assert_eq!(framed.into_inner().output.into_inner(), b"\x01b");
+}
+
+#[test]
+fn reunite_rejects_mismatched_halves() {
+    let (read, _) = Framed::new(Pipe::default(), LengthCodec::<u8>::new()).split();
+    let (_, write) = Framed::new(Pipe::default(), LengthCodec::<u8>::new()).split();
+    assert!(read.reunite(write).is_err());
+}
@@ -93,3 +93,96 @@ fn send_flushes_at_backpressure_boundary() {
     assert_eq!(io.num_poll_write, 2);
     assert_eq!(io.last_write_size, 9999 - 8 * 1024);
 }
+
+#[test]
+fn send_flushes_at_configured_high_water_mark() {
+    use async_codec_lite::{BytesCodec, FramedConfig, FramedWrite};
+    use futures_lite::future::block_on;
+    use futures_util::{
+        sink::SinkExt,
+        stream::{self, StreamExt},
+    };
+
+    let mut stream = stream::iter(ZeroBytes { count: 0, limit: 9999 }).map(Ok);
+    let io = AsyncWriteNull {
+        num_poll_write: 0,
+        last_write_size: 0,
+    };
+    let config = FramedConfig::new().write_capacity(1024).write_high_water_mark(1024);
+    let mut framer = FramedWrite::with_config(io, BytesCodec {}, config);
+    block_on(framer.send_all(&mut stream)).unwrap();
+    let io = framer.into_inner();
+    assert_eq!(io.num_poll_write, 10);
+    assert_eq!(io.last_write_size, 9999 - 9 * 1024);
+}
+
+struct RecordVectored {
+    written: Vec<u8>,
+    payload_ptrs: Vec<*const u8>,
+}
+impl AsyncWrite for RecordVectored {
+    fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        self.written.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        let mut len = 0;
+        for buf in bufs {
+            self.payload_ptrs.push(buf.as_ptr());
+            self.written.extend_from_slice(buf);
+            len += buf.len();
+        }
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[test]
+fn send_writes_large_payloads_without_copying() {
+    use async_codec_lite::{FramedWrite, LengthCodec};
+    use futures_lite::future::block_on;
+    use futures_util::sink::SinkExt;
+
+    let payload = Bytes::from(vec![7u8; 64 * 1024]);
+    let io = RecordVectored {
+        written: Vec::new(),
+        payload_ptrs: Vec::new(),
+    };
+    let mut framer = FramedWrite::new(io, LengthCodec::<u32>::new());
+    block_on(framer.send(payload.clone())).unwrap();
+    block_on(framer.send(Bytes::from_static(b"tail"))).unwrap();
+    let io = framer.into_inner();
+    assert!(io.payload_ptrs.contains(&payload.as_ptr()));
+    assert_eq!(&io.written[.. 4], &[0, 1, 0, 0]);
+    assert_eq!(&io.written[4 .. 4 + payload.len()], &payload[..]);
+    assert_eq!(&io.written[4 + payload.len() ..], b"\0\0\0\x04tail");
+}
+
+#[test]
+fn parts_keep_unflushed_frames() {
+    use async_codec_lite::{BytesCodec, FramedWrite};
+    use futures_lite::future::block_on;
+    use futures_util::{io::Cursor, sink::SinkExt};
+
+    let mut framer = FramedWrite::new(Cursor::new(Vec::new()), BytesCodec {});
+    framer.start_send_unpin(Bytes::from_static(b"hello")).unwrap();
+    assert_eq!(framer.write_buffer().len(), 5);
+
+    let parts = framer.into_parts();
+    assert_eq!(&parts.write_buf[..], b"hello");
+    let mut framer = FramedWrite::from_parts(parts);
+    block_on(framer.flush()).unwrap();
+    assert_eq!(framer.get_ref().get_ref(), b"hello");
+}
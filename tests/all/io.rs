@@ -0,0 +1,168 @@
+use async_codec_lite::{Bytes, FramedRead, FramedWrite, LengthCodec};
+use futures_lite::future::block_on;
+use futures_util::{
+    io::{AsyncRead, AsyncReadExt, Cursor},
+    sink::SinkExt,
+    stream::StreamExt,
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+struct OneByteAtATime(Vec<u8>, usize);
+
+impl AsyncRead for OneByteAtATime {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let pos = self.1;
+        if pos == self.0.len() {
+            return Poll::Ready(Ok(0));
+        }
+        buf[0] = self.0[pos];
+        self.1 += 1;
+        Poll::Ready(Ok(1))
+    }
+}
+
+// Accepts at most a few bytes per write.
+#[derive(Default)]
+struct ShortWrites(Vec<u8>);
+
+impl futures_util::io::AsyncWrite for ShortWrites {
+    fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let len = buf.len().min(7);
+        self.0.extend_from_slice(&buf[.. len]);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+// Bytes that don't compress, so the compressed output outgrows the drain threshold.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 1u32;
+    (0 .. len)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect()
+}
+
+fn compress<W: futures_util::io::AsyncWrite + Unpin>(writer: W, data: &[u8]) -> W {
+    use futures_util::io::AsyncWriteExt;
+    let mut writer = writer;
+    block_on(writer.write_all(data)).unwrap();
+    block_on(writer.close()).unwrap();
+    writer
+}
+
+#[cfg(feature = "gzip")]
+mod gzip {
+    use super::*;
+    use async_codec_lite::{GzipReader, GzipWriter};
+
+    #[test]
+    fn flushed_frames_reach_the_peer() {
+        let mut framed = FramedWrite::new(GzipWriter::gzip(Cursor::new(Vec::new())), LengthCodec::<u16>::new());
+        block_on(framed.send(Bytes::from("msg1"))).unwrap();
+        block_on(framed.send(Bytes::from("msg2"))).unwrap();
+
+        // The stream has not been closed, so only sync-flushed data is available.
+        let sent = framed.into_inner().into_inner().into_inner();
+        let framed = FramedRead::new(GzipReader::gzip(Cursor::new(sent)), LengthCodec::<u16>::new());
+        let msgs = block_on(framed.take(2).map(|res| res.unwrap()).collect::<Vec<_>>());
+        assert_eq!(msgs, vec![Bytes::from("msg1"), Bytes::from("msg2")]);
+    }
+
+    #[test]
+    fn closed_stream_roundtrips() {
+        let payload = Bytes::from(vec![b'x'; 100_000]);
+        let mut framed = FramedWrite::new(GzipWriter::gzip(Cursor::new(Vec::new())), LengthCodec::<u32>::new());
+        block_on(framed.send(payload.clone())).unwrap();
+        block_on(framed.close()).unwrap();
+
+        let sent = framed.into_inner().into_inner().into_inner();
+        assert!(sent.len() < payload.len());
+        let mut framed = FramedRead::new(GzipReader::gzip(Cursor::new(sent)), LengthCodec::<u32>::new());
+        assert_eq!(block_on(framed.next()).unwrap().unwrap(), payload);
+        assert!(block_on(framed.next()).is_none());
+    }
+
+    #[test]
+    fn decodes_input_split_at_every_byte() {
+        let sent = compress(GzipWriter::gzip(Cursor::new(Vec::new())), b"hello, world");
+        let sent = sent.into_inner().into_inner();
+        let mut out = Vec::new();
+        block_on(GzipReader::gzip(OneByteAtATime(sent, 0)).read_to_end(&mut out)).unwrap();
+        assert_eq!(out, b"hello, world");
+    }
+
+    #[test]
+    fn truncated_stream_is_an_error() {
+        let sent = compress(GzipWriter::gzip(Cursor::new(Vec::new())), &[b'x'; 1000]);
+        let mut sent = sent.into_inner().into_inner();
+        sent.truncate(sent.len() - 4);
+        let mut out = Vec::new();
+        let err = block_on(GzipReader::gzip(Cursor::new(sent)).read_to_end(&mut out)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut out = Vec::new();
+        block_on(GzipReader::gzip(Cursor::new(Vec::new())).read_to_end(&mut out)).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn short_writes_roundtrip() {
+        let data = noise(200_000);
+        let sent = compress(GzipWriter::gzip(ShortWrites::default()), &data).into_inner().0;
+        let mut out = Vec::new();
+        block_on(GzipReader::gzip(Cursor::new(sent)).read_to_end(&mut out)).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn writes_after_close_fail() {
+        use futures_util::io::AsyncWriteExt;
+
+        let mut writer = compress(GzipWriter::gzip(Cursor::new(Vec::new())), b"done");
+        assert!(block_on(writer.write(b"more")).is_err());
+    }
+}
+
+#[cfg(feature = "zstd")]
+mod zstd {
+    use super::*;
+    use async_codec_lite::{ZstdReader, ZstdWriter};
+
+    #[test]
+    fn flushed_frames_reach_the_peer() {
+        let writer = ZstdWriter::zstd(Cursor::new(Vec::new())).unwrap();
+        let mut framed = FramedWrite::new(writer, LengthCodec::<u16>::new());
+        block_on(framed.send(Bytes::from("msg1"))).unwrap();
+        block_on(framed.send(Bytes::from("msg2"))).unwrap();
+
+        let sent = framed.into_inner().into_inner().into_inner();
+        let reader = ZstdReader::zstd(Cursor::new(sent)).unwrap();
+        let framed = FramedRead::new(reader, LengthCodec::<u16>::new());
+        let msgs = block_on(framed.take(2).map(|res| res.unwrap()).collect::<Vec<_>>());
+        assert_eq!(msgs, vec![Bytes::from("msg1"), Bytes::from("msg2")]);
+    }
+
+    #[test]
+    fn truncated_stream_is_an_error() {
+        let sent = compress(ZstdWriter::zstd(Cursor::new(Vec::new())).unwrap(), &[b'x'; 1000]);
+        let mut sent = sent.into_inner().into_inner();
+        sent.truncate(sent.len() - 2);
+        let mut out = Vec::new();
+        let err = block_on(ZstdReader::zstd(OneByteAtATime(sent, 0)).unwrap().read_to_end(&mut out)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
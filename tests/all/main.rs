@@ -1,2 +1,4 @@
 mod codec;
 mod framed;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod io;